@@ -25,6 +25,7 @@ fn main() {
             "src/cpp/mqtt.cpp",
             "src/cpp/handle.cpp",
             "src/cpp/common.cpp",
+            "src/cpp/logs.cpp",
             "src/cpp/tunnel.cpp",
         ])
         .compile("aws-sdk-wrapper");
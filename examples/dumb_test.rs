@@ -31,7 +31,7 @@ async fn main() {
 
     loop {
         let msg = client
-            .publish("coooler", Qos::AtLeastOnce, false, "data".as_bytes())
+            .publish("coooler", Qos::AtLeastOnce, false, "data".as_bytes(), None)
             .await;
 
         info!("Message {msg:?}");
@@ -1,3 +1,5 @@
+use std::net::SocketAddr;
+
 use beluga::tunnel::TunnelClient;
 use beluga::{ConfigBuilder, MqttClient, Qos};
 use tracing::Level;
@@ -30,9 +32,14 @@ async fn main() {
     .await
     .unwrap();
 
-    let _tunnel_client = TunnelClient::create(client.clone(), Qos::AtLeastOnce, "...")
-        .await
-        .unwrap();
+    let _tunnel_client = TunnelClient::create(
+        client.clone(),
+        Qos::AtLeastOnce,
+        "...",
+        SocketAddr::from(([127, 0, 0, 1], 22)),
+    )
+    .await
+    .unwrap();
 
     let _ = tokio::signal::ctrl_c().await;
 }
@@ -1,6 +1,16 @@
 use std::ffi::{c_char, CStr};
 
-use tracing::{debug, error, info};
+use tracing::level_filters::LevelFilter;
+use tracing::{debug, error, info, trace, warn, Level};
+
+// Values of the `aws_log_level` enum from aws-c-common.
+const AWS_LL_NONE: i32 = 0;
+const AWS_LL_FATAL: i32 = 1;
+const AWS_LL_ERROR: i32 = 2;
+const AWS_LL_WARN: i32 = 3;
+const AWS_LL_INFO: i32 = 4;
+const AWS_LL_DEBUG: i32 = 5;
+const AWS_LL_TRACE: i32 = 6;
 
 #[no_mangle]
 extern "C" fn info(msg: *const c_char) {
@@ -16,3 +26,36 @@ extern "C" fn debug(msg: *const c_char) {
 extern "C" fn error(msg: *const c_char) {
     error!("{}", unsafe { CStr::from_ptr(msg) }.to_string_lossy());
 }
+
+/// Receives a record from the aws-c logger installed by the C++ side and
+/// re-emits it as a `tracing` event, the log subject (e.g. `mqtt-client`) is
+/// attached as the `subject` field.
+#[no_mangle]
+extern "C" fn forward_aws_log(level: i32, subject: *const c_char, msg: *const c_char) {
+    let subject = unsafe { CStr::from_ptr(subject) }.to_string_lossy();
+    let msg = unsafe { CStr::from_ptr(msg) }.to_string_lossy();
+
+    match level {
+        AWS_LL_FATAL | AWS_LL_ERROR => error!(%subject, "{msg}"),
+        AWS_LL_WARN => warn!(%subject, "{msg}"),
+        AWS_LL_INFO => info!(%subject, "{msg}"),
+        AWS_LL_DEBUG => debug!(%subject, "{msg}"),
+        AWS_LL_TRACE => trace!(%subject, "{msg}"),
+        _ => {}
+    }
+}
+
+/// Maps the maximum level enabled by the current `tracing` subscriber to the
+/// aws-c log level, so the CRT doesn't format records which would be
+/// discarded anyway.
+#[no_mangle]
+extern "C" fn max_aws_log_level() -> i32 {
+    match LevelFilter::current().into_level() {
+        Some(level) if level == Level::TRACE => AWS_LL_TRACE,
+        Some(level) if level == Level::DEBUG => AWS_LL_DEBUG,
+        Some(level) if level == Level::INFO => AWS_LL_INFO,
+        Some(level) if level == Level::WARN => AWS_LL_WARN,
+        Some(_) => AWS_LL_ERROR,
+        None => AWS_LL_NONE,
+    }
+}
@@ -1,10 +1,13 @@
 #include <aws/crt/Api.h>
+#include "logs.h"
 
 extern "C"
 {
     Aws::Crt::ApiHandle *create_api_handle()
     {
-        return new Aws::Crt::ApiHandle();
+        auto handle = new Aws::Crt::ApiHandle();
+        install_aws_logger();
+        return handle;
     }
 
     void drop_api_handle(Aws::Crt::ApiHandle *handle)
@@ -0,0 +1,63 @@
+#include <aws/common/logging.h>
+#include <cstdarg>
+#include <cstdio>
+#include <string>
+#include "logs.h"
+
+extern "C" void forward_aws_log(int level, const char *subject, const char *msg);
+extern "C" int max_aws_log_level();
+
+namespace
+{
+    int s_log(aws_logger *, aws_log_level level, aws_log_subject_t subject, const char *format, ...)
+    {
+        va_list args;
+        va_start(args, format);
+
+        va_list args_copy;
+        va_copy(args_copy, args);
+        int size_s = std::vsnprintf(nullptr, 0, format, args_copy);
+        va_end(args_copy);
+
+        if (size_s < 0)
+        {
+            va_end(args);
+            return AWS_OP_ERR;
+        }
+
+        std::string msg(static_cast<size_t>(size_s) + 1, '\0'); // Extra space for '\0'
+        std::vsnprintf(msg.data(), msg.size(), format, args);
+        va_end(args);
+        msg.resize(static_cast<size_t>(size_s)); // We don't want the '\0' inside
+
+        forward_aws_log(level, aws_log_subject_name(subject), msg.c_str());
+        return AWS_OP_SUCCESS;
+    }
+
+    aws_log_level s_get_log_level(aws_logger *, aws_log_subject_t)
+    {
+        return static_cast<aws_log_level>(max_aws_log_level());
+    }
+
+    void s_clean_up(aws_logger *)
+    {
+    }
+
+    int s_set_log_level(aws_logger *, aws_log_level)
+    {
+        // the level is driven by the `tracing` subscriber on the Rust side
+        return AWS_OP_SUCCESS;
+    }
+
+    aws_logger_vtable s_vtable = {s_log, s_get_log_level, s_clean_up, s_set_log_level};
+    aws_logger s_logger = {&s_vtable, nullptr, nullptr};
+}
+
+/**
+ * Installs the global aws-c logger which forwards all the records of the CRT libraries into `tracing`.
+ */
+void install_aws_logger()
+{
+    s_logger.allocator = aws_default_allocator();
+    aws_logger_set(&s_logger);
+}
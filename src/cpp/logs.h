@@ -6,6 +6,8 @@ extern "C" void info(const char *const msg);
 extern "C" void debug(const char *const msg);
 extern "C" void error(const char *const msg);
 
+void install_aws_logger();
+
 /**
  * The function is a variadic template function that takes a format string and a variable number of arguments.
  *
@@ -1,3 +1,4 @@
+#include <atomic>
 #include "mqtt.h"
 #include "logs.h"
 
@@ -9,7 +10,8 @@ extern "C" void on_completed(const void *, int, ReturnCode, bool);
 extern "C" void on_closed(const void *);
 extern "C" void on_interrupted(const void *, int);
 extern "C" void on_resumed(const void *, ReturnCode, bool);
-extern "C" void on_message(const void *, const char *, Buffer, bool, QOS, bool);
+extern "C" void on_message(const void *, const char *, const char *, Buffer, bool, QOS, bool);
+extern "C" void on_any_message(const void *, size_t);
 extern "C" void on_sub_ack(const void *, uint16_t, const char *, QOS, int);
 extern "C" void on_publish(const void *, uint16_t, int);
 extern "C" void on_unsubscribe(const void *, uint16_t, int);
@@ -27,12 +29,96 @@ const void *InternalMqttClient::get_interface() const
     return this->interface;
 }
 
+/**
+ * Resubscribes to all the topics the connection is currently subscribed to.
+ *
+ * @param connection connection which subscriptions should be restored
+ * @param interface interface to report the sub acks to
+ */
+void resubscribe(MqttConnection &connection, const void *interface)
+{
+    auto packet_id = connection.Resubscribe(
+        [=](MqttConnection &, uint16_t packet_id, const Aws::Crt::Vector<AwsString> &topics, QOS qos, int error_code)
+        {
+            for (const auto &topic : topics)
+            {
+                on_sub_ack(interface, packet_id, topic.c_str(), qos, error_code);
+            }
+        }
+    );
+
+    if (packet_id == 0)
+    {
+        debug(format("nothing was resubscribed, last error:[%d]", connection.LastError()).c_str());
+    }
+}
+
 extern "C" InternalMqttClient *internal_mqtt_client(ClientConfig client_config, const void *interface)
 {
     debug("start building internal mqtt client");
 
     auto config_builder = MqttBuilder();
-    if (!client_config.certificate.is_empty() && !client_config.private_key.is_empty())
+    if (client_config.websocket_region != nullptr)
+    {
+        std::shared_ptr<Aws::Crt::Auth::ICredentialsProvider> credentials_provider;
+        if (client_config.websocket_access_key_id != nullptr)
+        {
+            auto credentials_config = Aws::Crt::Auth::CredentialsProviderStaticConfig();
+            credentials_config.AccessKeyId = Aws::Crt::ByteCursorFromCString(client_config.websocket_access_key_id);
+            credentials_config.SecretAccessKey = Aws::Crt::ByteCursorFromCString(client_config.websocket_secret_access_key);
+            if (client_config.websocket_session_token != nullptr)
+            {
+                credentials_config.SessionToken = Aws::Crt::ByteCursorFromCString(client_config.websocket_session_token);
+            }
+            credentials_provider = Aws::Crt::Auth::CredentialsProvider::CreateCredentialsProviderStatic(credentials_config);
+        }
+        else
+        {
+            // environment variables, profile files, ECS or EC2 instance metadata
+            auto credentials_config = Aws::Crt::Auth::CredentialsProviderChainDefaultConfig();
+            credentials_config.Bootstrap = Aws::Crt::ApiHandle::GetOrCreateStaticDefaultClientBootstrap();
+            credentials_provider = Aws::Crt::Auth::CredentialsProvider::CreateCredentialsProviderChainDefault(credentials_config);
+        }
+
+        if (!credentials_provider)
+        {
+            error(format("couldn't create a credentials provider, last error:[%d]", Aws::Crt::LastError()).c_str());
+            return nullptr;
+        }
+
+        // the upgrade request is signed with SigV4, the client authenticates with the credentials rather than a certificate
+        config_builder = MqttBuilder(Aws::Iot::WebsocketConfig(client_config.websocket_region, credentials_provider));
+    }
+    else if (client_config.pkcs11_lib != nullptr)
+    {
+        auto pkcs11_lib = Aws::Crt::Io::Pkcs11Lib::Create(client_config.pkcs11_lib);
+        if (!pkcs11_lib)
+        {
+            error(format("couldn't load the pkcs11 library, last error:[%d]", Aws::Crt::LastError()).c_str());
+            return nullptr;
+        }
+
+        auto pkcs11_options = Aws::Crt::Io::TlsContextPkcs11Options(pkcs11_lib);
+        pkcs11_options.SetUserPin(client_config.pkcs11_user_pin);
+        if (client_config.pkcs11_token_label != nullptr)
+        {
+            pkcs11_options.SetTokenLabel(client_config.pkcs11_token_label);
+        }
+        if (client_config.pkcs11_has_slot_id)
+        {
+            pkcs11_options.SetSlotId(client_config.pkcs11_slot_id);
+        }
+        if (client_config.pkcs11_private_key_label != nullptr)
+        {
+            pkcs11_options.SetPrivateKeyObjectLabel(client_config.pkcs11_private_key_label);
+        }
+        pkcs11_options.SetCertificateFileContents(
+            AwsString(reinterpret_cast<const char *>(client_config.certificate.data), client_config.certificate.len)
+        );
+
+        config_builder = MqttBuilder(pkcs11_options);
+    }
+    else if (!client_config.certificate.is_empty() && !client_config.private_key.is_empty())
     {
         config_builder = MqttBuilder(
             Aws::Crt::ByteCursorFromByteBuf(client_config.certificate.into()),
@@ -46,16 +132,40 @@ extern "C" InternalMqttClient *internal_mqtt_client(ClientConfig client_config,
     }
     else
     {
-        error("config is missing password auth or pub/priv key auth");
+        error("config is missing password auth, pub/priv key auth, pkcs11 auth or websocket auth");
         return nullptr;
     }
 
+    if (!client_config.ca.is_empty())
+    {
+        config_builder.WithCertificateAuthority(Aws::Crt::ByteCursorFromByteBuf(client_config.ca.into()));
+    }
+
     config_builder.WithEndpoint(AwsString(client_config.endpoint));
     if (client_config.port != 0)
     {
         config_builder.WithPortOverride(client_config.port);
     }
 
+    if (client_config.proxy_host != nullptr)
+    {
+        auto proxy_options = Aws::Crt::Http::HttpClientConnectionProxyOptions();
+        proxy_options.HostName = client_config.proxy_host;
+        proxy_options.Port = client_config.proxy_port;
+        proxy_options.ProxyConnectionType = Aws::Crt::Http::AwsHttpProxyConnectionType::Tunneling;
+
+        if (client_config.proxy_username != nullptr)
+        {
+            auto auth_config = Aws::Crt::Http::HttpProxyStrategyBasicAuthConfig();
+            auth_config.ConnectionType = Aws::Crt::Http::AwsHttpProxyConnectionType::Tunneling;
+            auth_config.Username = client_config.proxy_username;
+            auth_config.Password = client_config.proxy_password;
+            proxy_options.ProxyStrategy = Aws::Crt::Http::HttpProxyStrategy::CreateBasicHttpProxyStrategy(auth_config);
+        }
+
+        config_builder.WithHttpProxyOptions(proxy_options);
+    }
+
     const auto config = config_builder.Build();
     if (!config)
     {
@@ -71,6 +181,41 @@ extern "C" InternalMqttClient *internal_mqtt_client(ClientConfig client_config,
         return nullptr;
     }
 
+    if (client_config.will_topic != nullptr &&
+        !connection->SetWill(client_config.will_topic, client_config.will_qos, client_config.will_retain, client_config.will_payload.into()))
+    {
+        error(format("couldn't set the will message, last error:[%d]", connection->LastError()).c_str());
+        return nullptr;
+    }
+
+    if (client_config.reconnect_min_backoff_s != 0 && client_config.reconnect_max_backoff_s != 0 &&
+        !connection->SetReconnectTimeout(client_config.reconnect_min_backoff_s, client_config.reconnect_max_backoff_s))
+    {
+        error(format("couldn't set the reconnect backoff, last error:[%d]", connection->LastError()).c_str());
+        return nullptr;
+    }
+
+    if (client_config.reconnect_max_attempts != 0)
+    {
+        // the sdk reconnects indefinitely, so let's count failed attempts ourselves and give up once the limit is reached
+        auto failed_attempts = std::make_shared<std::atomic<uint32_t>>(0);
+        const auto max_attempts = client_config.reconnect_max_attempts;
+
+        connection->OnConnectionSuccess = [=](MqttConnection &, Aws::Crt::Mqtt::OnConnectionSuccessData *)
+        {
+            failed_attempts->store(0);
+        };
+
+        connection->OnConnectionFailure = [=](MqttConnection &failed_connection, Aws::Crt::Mqtt::OnConnectionFailureData *)
+        {
+            if (failed_attempts->fetch_add(1) + 1 >= max_attempts)
+            {
+                error(format("giving up reconnecting after %u failed attempts", max_attempts).c_str());
+                failed_connection.Disconnect();
+            }
+        };
+    }
+
     connection->OnConnectionCompleted = [=](MqttConnection &, int error_code, ReturnCode return_code, bool session_present)
     {
         debug("internal mqtt client: on completed");
@@ -89,12 +234,28 @@ extern "C" InternalMqttClient *internal_mqtt_client(ClientConfig client_config,
         on_interrupted(interface, error);
     };
 
-    connection->OnConnectionResumed = [=](MqttConnection &, ReturnCode return_code, bool session_present)
+    connection->OnConnectionResumed = [=](MqttConnection &resumed_connection, ReturnCode return_code, bool session_present)
     {
         debug("internal mqtt client: on resumed");
+        // the broker has forgotten our subscriptions, so let's restore them with the qos they were made with
+        if (return_code == AWS_MQTT_CONNECT_ACCEPTED && !session_present)
+        {
+            resubscribe(resumed_connection, interface);
+        }
         on_resumed(interface, return_code, session_present);
     };
 
+    // invoked once per received publish, whereas the handlers of the subscriptions are invoked once per matching filter
+    if (!connection->SetOnMessageHandler(
+            [=](MqttConnection &, const AwsString &, const ByteBuf &payload, bool, QOS, bool)
+            {
+                on_any_message(interface, payload.len);
+            }))
+    {
+        error(format("couldn't set the message handler, last error:[%d]", connection->LastError()).c_str());
+        return nullptr;
+    }
+
     if (!connection->Connect(client_config.client_id, client_config.clean_session, client_config.keep_alive_s, client_config.ping_timeout_ms))
     {
         error(format("error during connect: client_id:[%s], endpoint:[%s], last error:[%s]",
@@ -116,12 +277,15 @@ extern "C" InternalMqttClient *internal_mqtt_client(ClientConfig client_config,
  */
 extern "C" uint16_t subscribe(InternalMqttClient *client, const char *topic, QOS qos)
 {
+    // the handler reports the filter it has been registered for, so the message is dispatched only to the
+    // subscribers of this filter even if several subscribed filters match the topic
+    const auto filter = AwsString(topic);
     auto connection = client->get_connection();
     return connection->Subscribe(
         topic, qos,
         [=](MqttConnection &, const AwsString &topic, const ByteBuf &payload, bool dup, QOS qos, bool retain)
         {
-            on_message(client->get_interface(), topic.c_str(), Buffer(payload), dup, qos, retain);
+            on_message(client->get_interface(), filter.c_str(), topic.c_str(), Buffer(payload), dup, qos, retain);
         },
         [=](MqttConnection &, uint16_t packet_id, const AwsString &topic, QOS qos, int error_code)
         {
@@ -144,12 +308,13 @@ extern "C" uint16_t subscribe_multiple(InternalMqttClient *client, const char *c
     auto topics_vector = Aws::Crt::Vector<std::pair<const char *, Aws::Crt::Mqtt::OnMessageReceivedHandler>>();
     for (size_t i = 0; i < topics_len; ++i)
     {
+        const auto filter = AwsString(topics[i]);
         topics_vector.push_back(
             std::make_pair(
                 topics[i],
                 [=](MqttConnection &, const AwsString &topic, const ByteBuf &payload, bool dup, QOS qos, bool retain)
                 {
-                    on_message(client->get_interface(), topic.c_str(), Buffer(payload), dup, qos, retain);
+                    on_message(client->get_interface(), filter.c_str(), topic.c_str(), Buffer(payload), dup, qos, retain);
                 }
             )
         );
@@ -210,9 +375,14 @@ extern "C" uint16_t publish(InternalMqttClient *client, const char *topic, QOS q
     );
 }
 
-extern "C" void disconnect(InternalMqttClient *client)
+/**
+ * Initiates the disconnect of the connection.
+ *
+ * @return true if the disconnect has been initiated, the closed callback is invoked once it's done
+ */
+extern "C" bool disconnect(InternalMqttClient *client)
 {
-    client->get_connection()->Disconnect();
+    return client->get_connection()->Disconnect();
 }
 
 extern "C" void drop_client(InternalMqttClient *client)
@@ -1,7 +1,10 @@
 #pragma once
 #include <aws/crt/Api.h>
+#include <aws/crt/auth/Credentials.h>
 #include <aws/iot/MqttClient.h>
 #include <aws/crt/mqtt/MqttConnection.h>
+#include <aws/crt/http/HttpProxyStrategy.h>
+#include <aws/crt/io/Pkcs11.h>
 #include "common.h"
 
 using MqttBuilder = Aws::Iot::MqttClientConnectionConfigBuilder;
@@ -35,5 +38,27 @@ extern "C"
         const char *password;
         Buffer certificate;
         Buffer private_key;
+        Buffer ca;
+        const char *will_topic;
+        Buffer will_payload;
+        QOS will_qos;
+        bool will_retain;
+        const char *pkcs11_lib;
+        const char *pkcs11_user_pin;
+        const char *pkcs11_token_label;
+        bool pkcs11_has_slot_id;
+        uint64_t pkcs11_slot_id;
+        const char *pkcs11_private_key_label;
+        const char *proxy_host;
+        uint16_t proxy_port;
+        const char *proxy_username;
+        const char *proxy_password;
+        const char *websocket_region;
+        const char *websocket_access_key_id;
+        const char *websocket_secret_access_key;
+        const char *websocket_session_token;
+        uint64_t reconnect_min_backoff_s;
+        uint64_t reconnect_max_backoff_s;
+        uint32_t reconnect_max_attempts;
     };
 }
@@ -6,13 +6,15 @@ use std::ffi::{c_void, IntoStringError, NulError};
 use std::io::Error as IoError;
 use std::string::FromUtf8Error;
 use std::sync::OnceLock;
+use std::time::Duration;
 
 pub use common::{AwsMqttConnectReturnCode, AwsMqttError, Qos};
 pub use mqtt::{
-    ClientStatus, Config, ConfigBuilder, CreateMqttFuture, Message, MqttClient,
-    OperationResponseFuture, SubscribeMessageFuture,
+    ClientStatus, Config, ConfigBuilder, CreateMqttFuture, LagPolicy, Message, MqttClient,
+    MqttMetrics, OperationResponseFuture, SubscribeMessageStream, TypedSubscriber,
 };
 use thiserror::Error;
+use tokio::task::JoinError;
 use tokio::time::error::Elapsed;
 
 mod common;
@@ -58,7 +60,7 @@ pub enum Error {
     MqttClientCreate,
     #[error("couldn't create a tunnel client")]
     TunnelClientCreate,
-    #[error("couldn't create a SSH tunnel")]
+    #[error("couldn't create a tunnel")]
     TunnelCreate,
     #[error("the mqtt client hasn't connected")]
     NotConnected,
@@ -70,6 +72,12 @@ pub enum Error {
     AwsReceiveResponse,
     #[error("failure to receive a message from a subscribe future")]
     AwsReceiveMessage,
+    #[error("there isn't any pending message")]
+    NoPendingMessage,
+    #[error("the broker rejected the subscription")]
+    SubscribeRejected,
+    #[error("unsupported quality of service [{0}]")]
+    UnsupportedQos(Qos),
     #[error("miss endpoint for the Mqtt client")]
     MissEndpoint,
     #[error("miss client id for the Mqtt client")]
@@ -78,6 +86,8 @@ pub enum Error {
     MissCertificate,
     #[error("miss the client's private key")]
     MissPrivateKey,
+    #[error("invalid reconnect backoff [{0:?}, {1:?}]")]
+    InvalidReconnectBackoff(Duration, Duration),
     #[error("invalid topic [{0}]")]
     InvalidTopic(String),
     #[error(transparent)]
@@ -86,8 +96,18 @@ pub enum Error {
     UnrecognizedEnumValue(isize, &'static str),
     #[error("message hasn't any payload")]
     Payload,
+    #[error("couldn't decode the payload of a message on [{topic}]")]
+    Decode {
+        topic: String,
+        #[source]
+        source: serde_json::Error,
+    },
     #[error("miss service id for tunneling")]
     ServiceId,
+    #[error("couldn't send a message through the tunnel [{0}]")]
+    TunnelSendMessage(i32),
+    #[error(transparent)]
+    Join(#[from] JoinError),
     #[error(transparent)]
     IoError(#[from] IoError),
 }
@@ -1,16 +1,19 @@
 //! Defines several callbacks that could be called from C/C++ side
 
-use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
 use std::ffi::{c_char, CStr};
+use std::hash::{Hash, Hasher};
 use std::os::raw::c_void;
 use std::sync::Arc;
 
-use crossbeam::queue::SegQueue;
-use parking_lot::FairMutex;
+use parking_lot::{FairMutex, Mutex};
+use tokio::sync::broadcast;
 use tokio::sync::oneshot::Sender;
 use tracing::{debug, error};
 
 use super::client::Subscriber;
+use super::metrics::Metrics;
 use super::{ClientStatus, Message};
 use crate::common::{AwsMqttConnectReturnCode, AwsMqttError, Buffer, Qos};
 
@@ -71,6 +74,7 @@ extern "C" fn on_resumed(
 #[no_mangle]
 extern "C" fn on_message(
     interface: *const c_void,
+    filter: *const c_char,
     topic: *const c_char,
     data: Buffer,
     dup: bool,
@@ -78,7 +82,14 @@ extern "C" fn on_message(
     retain: bool,
 ) {
     call(interface, |interface| {
-        interface.message.as_ref()(topic, data, dup, qos, retain);
+        interface.message.as_ref()(filter, topic, data, dup, qos, retain);
+    });
+}
+
+#[no_mangle]
+extern "C" fn on_any_message(interface: *const c_void, payload_len: usize) {
+    call(interface, |interface| {
+        interface.any_message.as_ref()(payload_len);
     });
 }
 
@@ -109,8 +120,19 @@ extern "C" fn on_unsubscribe(interface: *const c_void, packet_id: u16, error_cod
     });
 }
 
+fn update_status(
+    status: &FairMutex<ClientStatus>,
+    status_tx: &broadcast::Sender<ClientStatus>,
+    new_status: ClientStatus,
+) {
+    *status.lock() = new_status;
+    // nobody listening to the status stream isn't an error
+    let _ = status_tx.send(new_status);
+}
+
 pub(super) fn create_completed_callback(
     status: Arc<FairMutex<ClientStatus>>,
+    status_tx: broadcast::Sender<ClientStatus>,
     notify_future: Arc<FairMutex<Option<Sender<ClientStatus>>>>,
 ) -> impl Fn(i32, AwsMqttConnectReturnCode, bool) {
     move |error_code, return_code, session_present| {
@@ -121,12 +143,12 @@ pub(super) fn create_completed_callback(
         }
 
         if error_code != 0 && !matches!(return_code, AwsMqttConnectReturnCode::Accepted) {
-            *status.lock() = ClientStatus::Closed;
+            update_status(&status, &status_tx, ClientStatus::Closed);
             if let Some(notify) = notify_future.lock().take() {
                 let _ = notify.send(ClientStatus::Closed);
             };
         } else {
-            *status.lock() = ClientStatus::Connected;
+            update_status(&status, &status_tx, ClientStatus::Connected);
             if let Some(notify) = notify_future.lock().take() {
                 let _ = notify.send(ClientStatus::Connected);
             };
@@ -134,74 +156,154 @@ pub(super) fn create_completed_callback(
     }
 }
 
-pub(super) fn create_closed_callback(status: Arc<FairMutex<ClientStatus>>) -> impl Fn() {
+pub(super) fn create_closed_callback(
+    status: Arc<FairMutex<ClientStatus>>,
+    status_tx: broadcast::Sender<ClientStatus>,
+) -> impl Fn() {
     move || {
         debug!("on closed triggered");
-        *status.lock() = ClientStatus::Closed;
+        update_status(&status, &status_tx, ClientStatus::Closed);
     }
 }
 
-pub(super) fn create_interrupted_callback(status: Arc<FairMutex<ClientStatus>>) -> impl Fn(i32) {
+pub(super) fn create_interrupted_callback(
+    status: Arc<FairMutex<ClientStatus>>,
+    status_tx: broadcast::Sender<ClientStatus>,
+    metrics: Arc<Metrics>,
+) -> impl Fn(i32) {
     move |error_code| {
         if let Ok(error) = AwsMqttError::try_from(error_code) {
             debug!(%error, "on interrupted triggered");
         } else {
             debug!(%error_code, "on interrupted triggered");
         }
-        *status.lock() = ClientStatus::Interrupted;
+        metrics.interrupted();
+        update_status(&status, &status_tx, ClientStatus::Interrupted);
     }
 }
 
 pub(super) fn create_resumed_callback(
     status: Arc<FairMutex<ClientStatus>>,
+    status_tx: broadcast::Sender<ClientStatus>,
+    metrics: Arc<Metrics>,
 ) -> impl Fn(AwsMqttConnectReturnCode, bool) {
     move |return_code, session_present| {
         debug!(%return_code, %session_present, "on resumed triggered");
         if let AwsMqttConnectReturnCode::Accepted = return_code {
-            *status.lock() = ClientStatus::Connected;
+            metrics.resumed();
+            update_status(&status, &status_tx, ClientStatus::Connected);
+        }
+    }
+}
+
+/// The window of the recently received QoS 1 messages. The SDK doesn't expose
+/// the packet id of a PUBLISH, so a redelivered message is recognised by its
+/// dup flag, filter, topic and payload.
+struct RecentMessages {
+    capacity: usize,
+    keys: VecDeque<u64>,
+}
+
+impl RecentMessages {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            keys: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Records the message, `true` is returned if it's a redelivery of a
+    /// message in the window.
+    fn is_redelivery(&mut self, filter: &str, message: &Message) -> bool {
+        if !matches!(message.qos, Qos::AtLeastOnce) {
+            return false;
+        }
+
+        let mut hasher = DefaultHasher::new();
+        (filter, &message.topic, &message.data).hash(&mut hasher);
+        let key = hasher.finish();
+
+        if message.dup && self.keys.contains(&key) {
+            return true;
         }
+
+        if self.keys.len() == self.capacity {
+            self.keys.pop_front();
+        }
+        self.keys.push_back(key);
+        false
     }
 }
 
 pub(super) fn create_message_callback(
-    subscribers: Arc<SegQueue<Subscriber>>,
-) -> impl Fn(*const c_char, Buffer, bool, Qos, bool) {
-    move |topic, data, dup, qos, retain| {
+    subscribers: Arc<FairMutex<Vec<Subscriber>>>,
+    duplicate_window: Option<usize>,
+) -> impl Fn(*const c_char, *const c_char, Buffer, bool, Qos, bool) {
+    let recent = duplicate_window.map(|window| Mutex::new(RecentMessages::new(window)));
+
+    move |filter, topic, data, dup, qos, retain| {
+        let filter = unsafe { CStr::from_ptr(filter) }.to_string_lossy();
         let topic = unsafe { CStr::from_ptr(topic) }
             .to_string_lossy()
             .to_string();
 
         let msg = Message {
-            topic: topic.clone(),
+            topic,
             data: data.into(),
             dup,
             qos,
             retain,
         };
 
-        let queue_size = subscribers.len();
-
-        // The code is iterating over the subscribers in a loop, checking if each
-        // subscriber's topic matches the received message's topic. If there is
-        // a match, the message is sent to that subscriber using the
-        // `send_message` method. If there is no match, but the subscriber is not
-        // closed, it is pushed back into the subscribers queue for future messages.
-        // This loop ensures that the message is delivered to all subscribers
-        // whose topics match the received message.
-        for _ in 0..queue_size {
-            if let Some(subscriber) = subscribers.pop() {
-                if subscriber.contains(&topic) {
-                    subscriber.send_message(msg.clone());
-                } else if !subscriber.is_closed() {
-                    subscribers.push(subscriber);
-                }
+        if let Some(recent) = &recent {
+            if recent.lock().is_redelivery(&filter, &msg) {
+                debug!(topic = %msg.topic, "redelivered message suppressed");
+                return;
             }
         }
+
+        // The SDK invokes the handler of every subscribed filter which matches
+        // the topic, so the message is sent only to the subscribers of the filter
+        // the handler belongs to. The subscribers which are closed, i.e. their
+        // streams have been dropped, and the ones disconnected by the lag policy
+        // are removed on the way.
+        subscribers.lock().retain(|subscriber| {
+            if subscriber.contains(&filter) && !subscriber.send_message(msg.clone()) {
+                return false;
+            }
+            !subscriber.is_closed()
+        });
+    }
+}
+
+pub(super) fn create_any_message_callback(metrics: Arc<Metrics>) -> impl Fn(usize) {
+    move |payload_len| metrics.received(payload_len)
+}
+
+/// Notifiers of the pending subscriptions, keyed by the packet id.
+pub(super) type SubAckNotifiers = Arc<FairMutex<HashMap<u16, Sender<(i32, Qos)>>>>;
+
+pub(super) fn create_sub_ack_callback(
+    notifiers: SubAckNotifiers,
+) -> impl Fn(u16, *const c_char, Qos, i32) {
+    move |packet_id, _, qos, error_code| {
+        if let Some(notify_future) = notifiers.lock().remove(&packet_id) {
+            let _ = notify_future.send((error_code, qos));
+        }
     }
 }
 
-pub(super) fn create_sub_ack_callback() -> impl Fn(u16, *const c_char, Qos, i32) {
-    move |_, _, _, _| {}
+pub(super) fn create_publish_callback(
+    notifiers: Arc<FairMutex<HashMap<u16, Sender<i32>>>>,
+    metrics: Arc<Metrics>,
+) -> impl Fn(u16, i32) {
+    let notify = create_notify_callback(notifiers);
+    move |packet_id, error_code| {
+        // Notifying first waits for the publish to register the operation, so its
+        // payload size has been recorded by then.
+        notify(packet_id, error_code);
+        metrics.published(packet_id, error_code);
+    }
 }
 
 pub(super) fn create_notify_callback(
@@ -214,12 +316,18 @@ pub(super) fn create_notify_callback(
     }
 }
 
+/// Receives the subscribed filter the message matched, its topic, payload, dup
+/// flag, quality of service and retain flag.
+pub(super) type MessageCallback =
+    Box<dyn Fn(*const c_char, *const c_char, Buffer, bool, Qos, bool)>;
+
 pub(super) struct Interface {
     pub(super) completed: Box<dyn Fn(i32, AwsMqttConnectReturnCode, bool)>,
     pub(super) closed: Box<dyn Fn()>,
     pub(super) interrupted: Box<dyn Fn(i32)>,
     pub(super) resumed: Box<dyn Fn(AwsMqttConnectReturnCode, bool)>,
-    pub(super) message: Box<dyn Fn(*const c_char, Buffer, bool, Qos, bool)>,
+    pub(super) message: MessageCallback,
+    pub(super) any_message: Box<dyn Fn(usize)>,
     pub(super) sub_ack: Box<dyn Fn(u16, *const c_char, Qos, i32)>,
     pub(super) publish: Box<dyn Fn(u16, i32)>,
     pub(super) unsubscribe: Box<dyn Fn(u16, i32)>,
@@ -1,27 +1,36 @@
 use std::collections::{HashMap, HashSet};
-use std::ffi::{c_char, c_void, CString, NulError};
-use std::future;
+use std::ffi::{c_char, c_void, CStr, CString, NulError};
+use std::future::{self, Future};
 use std::ops::Deref;
 use std::sync::Arc;
+use std::time::Duration;
 
 use ::futures::future::BoxFuture;
-use crossbeam::queue::SegQueue;
+use ::futures::{FutureExt, TryFutureExt};
 use itertools::Itertools;
 use parking_lot::{const_fair_mutex, const_mutex, FairMutex, Mutex};
+use serde::de::DeserializeOwned;
 use smallvec::SmallVec;
 use strum::{AsRefStr, Display, EnumString};
 use tokio::sync::*;
+use tokio::time::Instant;
+use tracing::warn;
 
 use super::callbacks::{
-    create_closed_callback, create_completed_callback, create_interrupted_callback,
-    create_message_callback, create_notify_callback, create_resumed_callback,
-    create_sub_ack_callback, Interface,
+    create_any_message_callback, create_closed_callback, create_completed_callback,
+    create_interrupted_callback, create_message_callback, create_notify_callback,
+    create_publish_callback, create_resumed_callback, create_sub_ack_callback, Interface,
+    SubAckNotifiers,
 };
-use super::Message;
+use super::futures::{SubAckFuture, TypedSubscriber};
+use super::metrics::{Metrics, MqttMetrics};
+use super::queue::{message_queue, MessageSender};
+use super::topic::{validate_topic, validate_topic_filter};
+use super::{ensure_supported_qos, LagPolicy, Message};
 use crate::common::{Buffer, SharedPtr};
 use crate::{
     ApiHandle, AwsMqttError, Config, CreateMqttFuture, Error, OperationResponseFuture, Qos, Result,
-    SubscribeMessageFuture,
+    SubscribeMessageStream,
 };
 
 extern "C" {
@@ -47,10 +56,13 @@ extern "C" {
         data: Buffer,
     ) -> u16;
 
-    fn disconnect(client: *const InternalMqttClient);
+    fn disconnect(client: *const InternalMqttClient) -> bool;
     fn drop_client(client: *const InternalMqttClient);
 }
 
+/// Amount of status changes kept for the receivers which haven't caught up yet.
+const STATUS_CHANNEL_CAPACITY: usize = 16;
+
 #[repr(C)]
 pub(super) struct ClientConfig {
     pub(super) endpoint: *const c_char,
@@ -63,6 +75,28 @@ pub(super) struct ClientConfig {
     pub(super) password: *const c_char,
     pub(super) certificate: Buffer,
     pub(super) private_key: Buffer,
+    pub(super) ca: Buffer,
+    pub(super) will_topic: *const c_char,
+    pub(super) will_payload: Buffer,
+    pub(super) will_qos: Qos,
+    pub(super) will_retain: bool,
+    pub(super) pkcs11_lib: *const c_char,
+    pub(super) pkcs11_user_pin: *const c_char,
+    pub(super) pkcs11_token_label: *const c_char,
+    pub(super) pkcs11_has_slot_id: bool,
+    pub(super) pkcs11_slot_id: u64,
+    pub(super) pkcs11_private_key_label: *const c_char,
+    pub(super) proxy_host: *const c_char,
+    pub(super) proxy_port: u16,
+    pub(super) proxy_username: *const c_char,
+    pub(super) proxy_password: *const c_char,
+    pub(super) websocket_region: *const c_char,
+    pub(super) websocket_access_key_id: *const c_char,
+    pub(super) websocket_secret_access_key: *const c_char,
+    pub(super) websocket_session_token: *const c_char,
+    pub(super) reconnect_min_backoff_s: u64,
+    pub(super) reconnect_max_backoff_s: u64,
+    pub(super) reconnect_max_attempts: u32,
 }
 
 #[repr(C)]
@@ -103,11 +137,11 @@ pub(super) struct Subscriber {
     // `SmallVec` allows for efficient storage of topics, with the ability to store up to 3
     // topics without allocating additional memory.
     topics: SmallVec<[SmallVec<[u8; 10]>; 3]>,
-    sender: oneshot::Sender<Message>,
+    sender: MessageSender,
 }
 
 impl Subscriber {
-    pub(super) fn new(topics: &[&str], sender: oneshot::Sender<Message>) -> Self {
+    pub(super) fn new(topics: &[&str], sender: MessageSender) -> Self {
         Self {
             topics: topics
                 .iter()
@@ -117,14 +151,21 @@ impl Subscriber {
         }
     }
 
-    pub(super) fn contains(&self, topic: &str) -> bool {
+    /// Checks whether the subscriber is subscribed to the topic filter.
+    pub(super) fn contains(&self, filter: &str) -> bool {
         self.topics
             .iter()
-            .any(|it| it.as_slice() == topic.as_bytes())
+            .any(|it| it.as_slice() == filter.as_bytes())
+    }
+
+    fn remove_topic(&mut self, topic: &str) {
+        self.topics.retain(|it| it.as_slice() != topic.as_bytes());
     }
 
-    pub(super) fn send_message(self, message: Message) {
-        let _ = self.sender.send(message);
+    /// Sends the message to the subscriber, `false` is returned if the
+    /// subscriber has been disconnected by the lag policy.
+    pub(super) fn send_message(&self, message: Message) -> bool {
+        self.sender.send(message)
     }
 
     pub(super) fn is_closed(&self) -> bool {
@@ -138,18 +179,24 @@ pub struct MqttClient {
     internal_client: Arc<Mutex<InternalMqttClientPointer>>,
     _interface: Arc<Mutex<Interface>>,
     status: Arc<FairMutex<ClientStatus>>,
+    status_tx: broadcast::Sender<ClientStatus>,
     publish_notifiers: Arc<FairMutex<HashMap<u16, oneshot::Sender<i32>>>>,
-    subscribers: Arc<SegQueue<Subscriber>>,
+    subscribers: Arc<FairMutex<Vec<Subscriber>>>,
     subscription: Arc<FairMutex<HashSet<String>>>,
+    sub_ack_notifiers: SubAckNotifiers,
     unsubscribe_notifiers: Arc<FairMutex<HashMap<u16, oneshot::Sender<i32>>>>,
+    metrics: Arc<Metrics>,
+    subscriber_capacity: Option<(usize, LagPolicy)>,
 }
 
 impl Drop for MqttClient {
     fn drop(&mut self) {
         unsafe {
-            // let's first try to disconnect the client with 150 milliseconds timeout
             let mut guard = self.internal_client.lock();
-            disconnect(guard.internal_client);
+            // the connection could be already closed by `MqttClient::disconnect`
+            if !matches!(*self.status.lock(), ClientStatus::Closed) {
+                disconnect(guard.internal_client);
+            }
             // drop the client itself
             drop_client(guard.internal_client);
             guard.internal_client = std::ptr::null();
@@ -163,24 +210,45 @@ impl MqttClient {
 
         let client_config = ClientConfig::from(&config);
         let status = Arc::new(const_fair_mutex(Default::default()));
+        let (status_tx, _) = broadcast::channel(STATUS_CHANNEL_CAPACITY);
         let publish_notifiers = Arc::new(const_fair_mutex(Default::default()));
-        let subscribers = Arc::new(SegQueue::new());
+        let subscribers = Arc::new(const_fair_mutex(Vec::new()));
         let subscription = Arc::new(const_fair_mutex(Default::default()));
+        let sub_ack_notifiers = Arc::new(const_fair_mutex(Default::default()));
         let unsubscribe_notifiers = Arc::new(const_fair_mutex(Default::default()));
+        let metrics = Arc::new(Metrics::default());
+        let subscriber_capacity = config.subscriber_capacity;
+        let duplicate_window = config.duplicate_window;
 
         let (client_tx, client_rx) = oneshot::channel::<ClientStatus>();
 
         let interface = Arc::new(const_mutex(Interface {
             completed: Box::new(create_completed_callback(
                 status.clone(),
+                status_tx.clone(),
                 Arc::new(const_fair_mutex(client_tx.into())),
             )),
-            closed: Box::new(create_closed_callback(status.clone())),
-            interrupted: Box::new(create_interrupted_callback(status.clone())),
-            resumed: Box::new(create_resumed_callback(status.clone())),
-            message: Box::new(create_message_callback(subscribers.clone())),
-            sub_ack: Box::new(create_sub_ack_callback()),
-            publish: Box::new(create_notify_callback(publish_notifiers.clone())),
+            closed: Box::new(create_closed_callback(status.clone(), status_tx.clone())),
+            interrupted: Box::new(create_interrupted_callback(
+                status.clone(),
+                status_tx.clone(),
+                metrics.clone(),
+            )),
+            resumed: Box::new(create_resumed_callback(
+                status.clone(),
+                status_tx.clone(),
+                metrics.clone(),
+            )),
+            message: Box::new(create_message_callback(
+                subscribers.clone(),
+                duplicate_window,
+            )),
+            any_message: Box::new(create_any_message_callback(metrics.clone())),
+            sub_ack: Box::new(create_sub_ack_callback(sub_ack_notifiers.clone())),
+            publish: Box::new(create_publish_callback(
+                publish_notifiers.clone(),
+                metrics.clone(),
+            )),
             unsubscribe: Box::new(create_notify_callback(unsubscribe_notifiers.clone())),
         }));
 
@@ -202,10 +270,14 @@ impl MqttClient {
                 _interface: interface,
                 internal_client,
                 status,
+                status_tx,
                 publish_notifiers,
                 subscribers,
                 subscription,
+                sub_ack_notifiers,
                 unsubscribe_notifiers,
+                metrics,
+                subscriber_capacity,
             },
             client_rx,
         ))
@@ -222,6 +294,8 @@ impl MqttClient {
     /// - `qos`: The `qos` parameter stands for Quality of Service. It
     ///   determines the level of guarantee for
     /// message delivery.
+    ///   Only [`Qos::AtMostOnce`] and [`Qos::AtLeastOnce`] are supported by AWS
+    ///   IoT Core, [`Error::UnsupportedQos`] is returned otherwise.
     /// - `retain`: The `retain` parameter determines whether the message should
     ///   be retained by the broker
     /// or not. If `retain` is set to `true`, the broker will store the last
@@ -232,7 +306,7 @@ impl MqttClient {
     /// - `timeout`: The `timeout` parameter is an optional duration that
     ///   specifies the maximum amount of
     /// time to wait for the publish operation to complete. If the timeout is
-    /// reached and the operation has not completed, an error will be
+    /// reached and the operation has not completed, [`Error::Timeout`] will be
     /// returned.
     ///
     /// # Returns:
@@ -244,15 +318,29 @@ impl MqttClient {
         qos: Qos,
         retain: bool,
         data: &[u8],
+        timeout: Option<Duration>,
     ) -> BoxFuture<'static, Result<()>> {
         if !self.is_connected() {
             return Box::pin(future::ready(Err(Error::NotConnected)));
         }
 
+        if let Err(err) = ensure_supported_qos(qos) {
+            return Box::pin(future::ready(Err(err)));
+        }
+
+        if let Err(err) = validate_topic(topic) {
+            return Box::pin(future::ready(Err(err)));
+        }
+
         let Ok(c_topic) = CString::new(topic) else {
             return Box::pin(future::ready(Err(Error::InvalidTopic(topic.to_owned()))));
         };
 
+        // The notifiers are locked till the notifier of the operation is registered,
+        // otherwise a quick acknowledgement could be missed and the future would never
+        // resolve.
+        let mut publish_notifiers = self.publish_notifiers.lock();
+
         let packet_id = {
             let guard = self.internal_client.lock();
             unsafe {
@@ -267,13 +355,44 @@ impl MqttClient {
         };
 
         if packet_id == 0 {
+            self.metrics.publish_failed();
             return Box::pin(future::ready(Err(AwsMqttError::ProtocolError.into())));
         }
 
-        let (publish_tx, publish_rx) = oneshot::channel::<i32>();
-        self.publish_notifiers.lock().insert(packet_id, publish_tx);
+        self.metrics.publish_sent(packet_id, data.len());
+        let publish_rx = register_notifier(&mut publish_notifiers, packet_id);
+
+        let metrics = self.metrics.clone();
+        with_timeout(timeout, OperationResponseFuture::new(publish_rx))
+            .inspect_err(move |err| {
+                if matches!(err, Error::Timeout(_)) {
+                    metrics.publish_timed_out();
+                }
+            })
+            .boxed()
+    }
 
-        Box::pin(OperationResponseFuture::new(publish_rx))
+    /// Publishes a message which the broker retains as the last known state of
+    /// the topic and delivers to every new subscriber, see
+    /// [`MqttClient::publish`].
+    pub fn publish_retained(
+        &self,
+        topic: &str,
+        qos: Qos,
+        data: &[u8],
+        timeout: Option<Duration>,
+    ) -> BoxFuture<'static, Result<()>> {
+        self.publish(topic, qos, true, data, timeout)
+    }
+
+    /// Removes the retained message of the topic from the broker by publishing
+    /// an empty retained message, see [`MqttClient::publish`].
+    pub fn clear_retained(
+        &self,
+        topic: &str,
+        timeout: Option<Duration>,
+    ) -> BoxFuture<'static, Result<()>> {
+        self.publish(topic, Qos::AtLeastOnce, true, &[], timeout)
     }
 
     /// Subscribes to a topic with a specified quality of service [`Qos`] and an
@@ -285,27 +404,46 @@ impl MqttClient {
     /// - `qos`: The `qos` parameter stands for Quality of Service. It is used
     ///   to specify the level of
     /// guarantee for message delivery.
+    ///   Only [`Qos::AtMostOnce`] and [`Qos::AtLeastOnce`] are supported by AWS
+    ///   IoT Core, [`Error::UnsupportedQos`] is returned otherwise.
     /// * `timeout`: The `timeout` parameter is an optional duration that
     ///   specifies the maximum amount of
     /// time to wait for a response from the server when subscribing to a topic.
     /// If the timeout is reached and no response is received within that
-    /// time, an error will be returned.
+    /// time, [`Error::Timeout`] will be returned.
     ///
     /// # Returns:
     ///
-    /// returns the [`Message`].
-    pub fn subscribe(&self, topic: &str, qos: Qos) -> BoxFuture<Result<Message>> {
+    /// returns the [`SubscribeMessageStream`] which yields every [`Message`]
+    /// received on the topic until it's dropped, once the subscription is
+    /// acknowledged by the broker.
+    pub fn subscribe(
+        &self,
+        topic: &str,
+        qos: Qos,
+        timeout: Option<Duration>,
+    ) -> BoxFuture<'static, Result<SubscribeMessageStream>> {
         if !self.is_connected() {
             return Box::pin(future::ready(Err(Error::NotConnected)));
         }
 
+        if let Err(err) = ensure_supported_qos(qos) {
+            return Box::pin(future::ready(Err(err)));
+        }
+
+        if let Err(err) = validate_topic_filter(topic) {
+            return Box::pin(future::ready(Err(err)));
+        }
+
         let Ok(c_topic) = CString::new(topic) else {
             return Box::pin(future::ready(Err(Error::InvalidTopic(topic.to_owned()))));
         };
 
         let mut subscription = self.subscription.lock();
 
-        if !subscription.contains(topic) {
+        let sub_ack = if !subscription.contains(topic) {
+            let mut sub_ack_notifiers = self.sub_ack_notifiers.lock();
+
             let packet_id = {
                 let guard = self.internal_client.lock();
                 unsafe { subscribe(guard.internal_client, c_topic.as_ptr(), qos) }
@@ -316,13 +454,47 @@ impl MqttClient {
             }
 
             subscription.insert(topic.to_owned());
-        }
+            Some(register_notifier(&mut sub_ack_notifiers, packet_id))
+        } else {
+            None
+        };
 
-        let (subscribe_tx, subscribe_rx) = oneshot::channel::<Message>();
+        let (subscribe_tx, subscribe_rx) =
+            message_queue(self.subscriber_capacity, self.metrics.clone());
         self.subscribers
+            .lock()
             .push(Subscriber::new(&[topic], subscribe_tx));
 
-        Box::pin(SubscribeMessageFuture::new(subscribe_rx))
+        self.subscribed(
+            sub_ack,
+            vec![topic.to_owned()],
+            SubscribeMessageStream::new(subscribe_rx),
+            timeout,
+        )
+    }
+
+    /// Subscribes to a topic like [`MqttClient::subscribe`], the JSON payload
+    /// of every received message is deserialized into `T`.
+    ///
+    /// # Arguments:
+    ///
+    /// - `topic`: A string representing the topic to subscribe to.
+    /// - `qos`: Quality of Service of the subscription.
+    /// - `timeout`: The maximum amount of time to wait for the subscription to
+    ///   be acknowledged, [`Error::Timeout`] is returned once it's reached.
+    ///
+    /// # Returns:
+    ///
+    /// returns the [`TypedSubscriber`] which yields the deserialized payloads.
+    pub fn subscribe_json<T: DeserializeOwned + 'static>(
+        &self,
+        topic: &str,
+        qos: Qos,
+        timeout: Option<Duration>,
+    ) -> BoxFuture<'static, Result<TypedSubscriber<T>>> {
+        self.subscribe(topic, qos, timeout)
+            .map_ok(TypedSubscriber::new)
+            .boxed()
     }
 
     /// Subscribes to multiple topics with a specified quality of service and an
@@ -335,20 +507,40 @@ impl MqttClient {
     /// - `qos`: The `qos` parameter stands for Quality of Service. It
     ///   determines the level of guarantee for
     /// message delivery between the client and the server.
+    ///   Only [`Qos::AtMostOnce`] and [`Qos::AtLeastOnce`] are supported by AWS
+    ///   IoT Core, [`Error::UnsupportedQos`] is returned otherwise.
     /// - `timeout`: The `timeout` parameter is an optional duration that
     ///   specifies the maximum amount of
     /// time to wait for the subscription to complete. If the subscription does
-    /// not complete within the specified timeout duration, an error will be
-    /// returned.
+    /// not complete within the specified timeout duration, [`Error::Timeout`]
+    /// will be returned.
     ///
     /// # Returns:
     ///
-    /// returns the [`Message`].
-    pub fn subscribe_multiple(&self, topics: &[&str], qos: Qos) -> BoxFuture<Result<Message>> {
+    /// returns the [`SubscribeMessageStream`] which yields every [`Message`]
+    /// received on any of the topics until it's dropped, once the subscription
+    /// is acknowledged by the broker.
+    pub fn subscribe_multiple(
+        &self,
+        topics: &[&str],
+        qos: Qos,
+        timeout: Option<Duration>,
+    ) -> BoxFuture<'static, Result<SubscribeMessageStream>> {
         if !self.is_connected() {
             return Box::pin(future::ready(Err(Error::NotConnected)));
         }
 
+        if let Err(err) = ensure_supported_qos(qos) {
+            return Box::pin(future::ready(Err(err)));
+        }
+
+        if let Err(err) = topics
+            .iter()
+            .try_for_each(|topic| validate_topic_filter(topic))
+        {
+            return Box::pin(future::ready(Err(err)));
+        }
+
         let mut subscription = self.subscription.lock();
 
         let topics_diff = topics
@@ -359,7 +551,7 @@ impl MqttClient {
             .map(ToOwned::to_owned)
             .collect_vec();
 
-        if !topics_diff.is_empty() {
+        let sub_ack = if !topics_diff.is_empty() {
             let Ok::<Vec<CString>, NulError>(c_str_topics) = topics_diff
                 .iter()
                 .map(|topic| CString::new(topic.as_str()))
@@ -375,13 +567,15 @@ impl MqttClient {
                 .map(|topic| topic.as_c_str().as_ptr())
                 .collect_vec();
 
+            let mut sub_ack_notifiers = self.sub_ack_notifiers.lock();
+
             let packet_id = {
                 let guard = self.internal_client.lock();
                 unsafe {
                     subscribe_multiple(
                         guard.internal_client,
                         topics_ptr.as_mut_ptr(),
-                        topics.len(),
+                        topics_ptr.len(),
                         qos,
                     )
                 }
@@ -391,13 +585,24 @@ impl MqttClient {
                 return Box::pin(future::ready(Err(AwsMqttError::ProtocolError.into())));
             }
 
-            subscription.extend(topics_diff);
-        }
-
-        let (subscribe_tx, subscribe_rx) = oneshot::channel::<Message>();
-        self.subscribers.push(Subscriber::new(topics, subscribe_tx));
+            subscription.extend(topics_diff.iter().cloned());
+            Some(register_notifier(&mut sub_ack_notifiers, packet_id))
+        } else {
+            None
+        };
 
-        Box::pin(SubscribeMessageFuture::new(subscribe_rx))
+        let (subscribe_tx, subscribe_rx) =
+            message_queue(self.subscriber_capacity, self.metrics.clone());
+        self.subscribers
+            .lock()
+            .push(Subscriber::new(topics, subscribe_tx));
+
+        self.subscribed(
+            sub_ack,
+            topics_diff,
+            SubscribeMessageStream::new(subscribe_rx),
+            timeout,
+        )
     }
 
     /// Unsubscribes from a specified topic.
@@ -407,34 +612,239 @@ impl MqttClient {
     /// - `topic`: The `topic` parameter is a string that represents the topic
     ///   from which the client
     /// wants to unsubscribe.
-    pub fn unsubscribe(&self, topic: &str) -> BoxFuture<Result<()>> {
+    /// - `timeout`: The `timeout` parameter is an optional duration that
+    ///   specifies the maximum amount of time to wait for the unsubscribe
+    ///   operation to complete. If the timeout is reached, [`Error::Timeout`]
+    ///   will be returned.
+    pub fn unsubscribe(
+        &self,
+        topic: &str,
+        timeout: Option<Duration>,
+    ) -> BoxFuture<'static, Result<()>> {
         if !self.is_connected() {
             return Box::pin(future::ready(Err(Error::NotConnected)));
         }
 
+        if let Err(err) = validate_topic_filter(topic) {
+            return Box::pin(future::ready(Err(err)));
+        }
+
         let Ok(c_topic) = CString::new(topic) else {
             return Box::pin(future::ready(Err(Error::InvalidTopic(topic.to_owned()))));
         };
 
+        self.drop_subscribers(&[topic]);
+
         if self.subscription.lock().remove(topic) {
-            let packet_id = {
-                let guard = self.internal_client.lock();
-                unsafe { unsubscribe(guard.internal_client, c_topic.as_ptr()) }
-            };
+            match self.send_unsubscribe(&c_topic) {
+                Ok(response) => with_timeout(timeout, response),
+                Err(err) => Box::pin(future::ready(Err(err))),
+            }
+        } else {
+            Box::pin(future::ready(Ok(())))
+        }
+    }
 
-            if packet_id == 0 {
-                return Box::pin(future::ready(Err(AwsMqttError::ProtocolError.into())));
+    /// Unsubscribes from multiple topics.
+    ///
+    /// The SDK's MQTT 3.1.1 connection accepts a single topic filter per
+    /// unsubscribe request, so a request is sent for every subscribed topic
+    /// and the returned future resolves once all of them are acknowledged.
+    ///
+    /// # Arguments:
+    ///
+    /// - `topics`: A slice of string references representing the topics to
+    ///   unsubscribe from.
+    /// - `timeout`: The `timeout` parameter is an optional duration that
+    ///   specifies the maximum amount of time to wait for all the unsubscribe
+    ///   operations to complete. If the timeout is reached, [`Error::Timeout`]
+    ///   will be returned.
+    pub fn unsubscribe_multiple(
+        &self,
+        topics: &[&str],
+        timeout: Option<Duration>,
+    ) -> BoxFuture<'static, Result<()>> {
+        if !self.is_connected() {
+            return Box::pin(future::ready(Err(Error::NotConnected)));
+        }
+
+        if let Err(err) = topics
+            .iter()
+            .try_for_each(|topic| validate_topic_filter(topic))
+        {
+            return Box::pin(future::ready(Err(err)));
+        }
+
+        let Ok::<Vec<CString>, NulError>(c_str_topics) = topics
+            .iter()
+            .map(|topic| CString::new(*topic))
+            .try_collect()
+        else {
+            return Box::pin(future::ready(Err(Error::InvalidTopic(
+                topics.iter().join(","),
+            ))));
+        };
+
+        self.drop_subscribers(topics);
+
+        let mut subscription = self.subscription.lock();
+        let mut responses = Vec::with_capacity(topics.len());
+
+        for (topic, c_topic) in topics.iter().zip(c_str_topics.iter()) {
+            if !subscription.remove(*topic) {
+                continue;
             }
 
-            let (unsubscribe_tx, unsubscribe_rx) = oneshot::channel::<i32>();
-            self.unsubscribe_notifiers
-                .lock()
-                .insert(packet_id, unsubscribe_tx);
+            match self.send_unsubscribe(c_topic) {
+                Ok(response) => responses.push(response),
+                Err(err) => return Box::pin(future::ready(Err(err))),
+            }
+        }
 
-            Box::pin(OperationResponseFuture::new(unsubscribe_rx))
-        } else {
-            Box::pin(future::ready(Ok(())))
+        with_timeout(
+            timeout,
+            ::futures::future::try_join_all(responses).map_ok(|_| ()),
+        )
+    }
+
+    /// Sends a request and waits for its response, following the request /
+    /// response pattern of the AWS IoT services, e.g. the jobs or fleet
+    /// provisioning APIs.
+    ///
+    /// The client subscribes to `response_topic_filter`, publishes the request
+    /// and resolves with the first message received on the filter. If the
+    /// request is a JSON object with a `clientToken`, only the responses
+    /// carrying the same token are considered. The filter is unsubscribed
+    /// afterwards, unless there is still another stream subscribed to it, e.g.
+    /// of a concurrent request.
+    ///
+    /// # Arguments:
+    ///
+    /// - `request_topic`: The topic the request is published to.
+    /// - `response_topic_filter`: The topic filter the response is expected on,
+    ///   e.g. `$aws/things/thing/jobs/get/+` to receive both the accepted and
+    ///   the rejected response.
+    /// - `payload`: Payload of the request.
+    /// - `timeout`: The maximum amount of time to wait for the response,
+    ///   [`Error::Timeout`] is returned once it's reached. The unsubscribe
+    ///   afterwards is bounded by the same deadline.
+    pub fn request(
+        self: &Arc<Self>,
+        request_topic: &str,
+        response_topic_filter: &str,
+        payload: &[u8],
+        timeout: Duration,
+    ) -> BoxFuture<'static, Result<Message>> {
+        let client = self.clone();
+        let request_topic = request_topic.to_owned();
+        let response_topic_filter = response_topic_filter.to_owned();
+        let payload = payload.to_vec();
+        let token = client_token(&payload);
+
+        Box::pin(async move {
+            let deadline = Instant::now() + timeout;
+
+            // the stream of the responses is dropped once the block is done, so it
+            // doesn't keep the filter in use
+            let response = tokio::time::timeout_at(deadline, async {
+                let mut responses = client
+                    .subscribe(&response_topic_filter, Qos::AtLeastOnce, None)
+                    .await?;
+                client
+                    .publish(&request_topic, Qos::AtLeastOnce, false, &payload, None)
+                    .await?;
+
+                while let Some(message) = responses.recv().await {
+                    if token.is_none() || client_token(&message.data) == token {
+                        return Ok(message);
+                    }
+                }
+                Err(Error::AwsReceiveMessage)
+            })
+            .await;
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if let Err(err) = client
+                .unsubscribe_unused(&response_topic_filter, remaining)
+                .await
+            {
+                warn!(%err, topic = %response_topic_filter, "couldn't unsubscribe after a request");
+            }
+
+            response?
+        })
+    }
+
+    /// Disconnects the client from the broker and waits till the connection is
+    /// closed, so in-flight operations are settled before the client is
+    /// released.
+    ///
+    /// # Arguments:
+    ///
+    /// - `timeout`: The maximum amount of time to wait for the connection to be
+    ///   closed. If the timeout is reached, [`Error::Timeout`] is returned.
+    ///
+    /// [`Error::NotConnected`] is returned right away if the SDK refuses to
+    /// disconnect, e.g. because the connection is already being closed.
+    pub fn disconnect(&self, timeout: Duration) -> BoxFuture<'static, Result<()>> {
+        if matches!(self.status(), ClientStatus::Closed) {
+            return Box::pin(future::ready(Ok(())));
+        }
+
+        // subscribe before disconnecting to not miss the closed event
+        let mut status_rx = self.status_tx.subscribe();
+        let status = self.status.clone();
+
+        let disconnecting = {
+            let guard = self.internal_client.lock();
+            unsafe { disconnect(guard.internal_client) }
+        };
+
+        // the sdk refuses to disconnect a connection which isn't connected or is
+        // already disconnecting, the closed event won't come in that case
+        if !disconnecting {
+            return Box::pin(future::ready(Err(Error::NotConnected)));
         }
+
+        Box::pin(async move {
+            tokio::time::timeout(timeout, async move {
+                loop {
+                    match status_rx.recv().await {
+                        Ok(ClientStatus::Closed) | Err(broadcast::error::RecvError::Closed) => {
+                            return;
+                        }
+                        Err(broadcast::error::RecvError::Lagged(_)) => {
+                            if matches!(*status.lock(), ClientStatus::Closed) {
+                                return;
+                            }
+                        }
+                        Ok(_) => {}
+                    }
+                }
+            })
+            .await
+            .map_err(Error::Timeout)
+        })
+    }
+
+    /// Returns the current status of the connection.
+    pub fn status(&self) -> ClientStatus {
+        *self.status.lock()
+    }
+
+    /// Returns a receiver of the connection status changes.
+    ///
+    /// Every completed, interrupted, resumed or closed event of the underlying
+    /// connection is broadcast to all the receivers, which allows to react on
+    /// reconnects without polling [`MqttClient::status`].
+    pub fn status_stream(&self) -> broadcast::Receiver<ClientStatus> {
+        self.status_tx.subscribe()
+    }
+
+    /// Returns a snapshot of the client's counters, e.g. the number of the
+    /// published and received messages or of the reconnects.
+    pub fn metrics(&self) -> MqttMetrics {
+        self.metrics.snapshot()
     }
 
     pub(crate) fn internal_client(&self) -> Arc<Mutex<InternalMqttClientPointer>> {
@@ -444,4 +854,131 @@ impl MqttClient {
     fn is_connected(&self) -> bool {
         matches!(*self.status.lock(), ClientStatus::Connected)
     }
+
+    /// Unsubscribes from the topic filter unless there is still a live
+    /// subscriber of it. The subscription lock is held while checking, so a
+    /// concurrent subscribe to the filter can't be cut off.
+    fn unsubscribe_unused(
+        &self,
+        filter: &str,
+        timeout: Duration,
+    ) -> BoxFuture<'static, Result<()>> {
+        let mut subscription = self.subscription.lock();
+
+        let in_use = self
+            .subscribers
+            .lock()
+            .iter()
+            .any(|subscriber| !subscriber.is_closed() && subscriber.contains(filter));
+
+        if in_use || !subscription.remove(filter) {
+            return Box::pin(future::ready(Ok(())));
+        }
+
+        let Ok(c_filter) = CString::new(filter) else {
+            return Box::pin(future::ready(Err(Error::InvalidTopic(filter.to_owned()))));
+        };
+
+        match self.send_unsubscribe(&c_filter) {
+            Ok(response) => with_timeout(Some(timeout), response),
+            Err(err) => Box::pin(future::ready(Err(err))),
+        }
+    }
+
+    /// Sends the unsubscribe request, the returned future resolves once it's
+    /// acknowledged.
+    fn send_unsubscribe(
+        &self,
+        topic: &CStr,
+    ) -> Result<OperationResponseFuture<oneshot::Receiver<i32>>> {
+        let mut unsubscribe_notifiers = self.unsubscribe_notifiers.lock();
+
+        let packet_id = {
+            let guard = self.internal_client.lock();
+            unsafe { unsubscribe(guard.internal_client, topic.as_ptr()) }
+        };
+
+        if packet_id == 0 {
+            return Err(AwsMqttError::ProtocolError.into());
+        }
+
+        let unsubscribe_rx = register_notifier(&mut unsubscribe_notifiers, packet_id);
+        Ok(OperationResponseFuture::new(unsubscribe_rx))
+    }
+
+    /// Removes the topics from the subscribers, the subscribers which aren't
+    /// interested in any topic anymore are dropped, so their streams end.
+    fn drop_subscribers(&self, topics: &[&str]) {
+        self.subscribers.lock().retain_mut(|subscriber| {
+            for topic in topics {
+                subscriber.remove_topic(topic);
+            }
+            !subscriber.topics.is_empty()
+        });
+    }
+
+    /// Waits for the acknowledgement of the subscription, if a subscribe
+    /// request has been sent, before handing out the stream. The topics are
+    /// forgotten on failure, so the next subscribe to them sends a new
+    /// request.
+    fn subscribed(
+        &self,
+        sub_ack: Option<oneshot::Receiver<(i32, Qos)>>,
+        topics: Vec<String>,
+        stream: SubscribeMessageStream,
+        timeout: Option<Duration>,
+    ) -> BoxFuture<'static, Result<SubscribeMessageStream>> {
+        let Some(sub_ack) = sub_ack else {
+            return Box::pin(future::ready(Ok(stream)));
+        };
+
+        let subscription = self.subscription.clone();
+        with_timeout(timeout, SubAckFuture::new(sub_ack))
+            .map(move |res| match res {
+                Ok(()) => Ok(stream),
+                Err(err) => {
+                    let mut subscription = subscription.lock();
+                    for topic in &topics {
+                        subscription.remove(topic);
+                    }
+                    Err(err)
+                }
+            })
+            .boxed()
+    }
+}
+
+/// Extracts the `clientToken` of a JSON payload, which the AWS IoT services
+/// echo in their responses.
+fn client_token(payload: &[u8]) -> Option<String> {
+    serde_json::from_slice::<serde_json::Value>(payload)
+        .ok()?
+        .get("clientToken")?
+        .as_str()
+        .map(ToOwned::to_owned)
+}
+
+/// Registers the notifier of the operation. The notifiers of the operations
+/// which futures have been dropped, e.g. because of a timeout, are removed on
+/// the way.
+fn register_notifier<T>(
+    notifiers: &mut HashMap<u16, oneshot::Sender<T>>,
+    packet_id: u16,
+) -> oneshot::Receiver<T> {
+    notifiers.retain(|_, notifier| !notifier.is_closed());
+
+    let (notifier_tx, notifier_rx) = oneshot::channel::<T>();
+    notifiers.insert(packet_id, notifier_tx);
+    notifier_rx
+}
+
+/// Bounds the operation with the timeout, if there is any.
+fn with_timeout<T>(
+    timeout: Option<Duration>,
+    operation: impl Future<Output = Result<T>> + Send + 'static,
+) -> BoxFuture<'static, Result<T>> {
+    match timeout {
+        Some(timeout) => Box::pin(async move { tokio::time::timeout(timeout, operation).await? }),
+        None => Box::pin(operation),
+    }
 }
@@ -1,14 +1,20 @@
-use std::future::Future;
+use std::future::{self, Future};
+use std::marker::PhantomData;
 use std::pin::Pin;
 use std::result::Result as StdResult;
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 
+use futures::Stream;
 use pin_project::pin_project;
+use serde::de::DeserializeOwned;
+use tokio::sync::mpsc::error::TryRecvError;
 use tokio::sync::oneshot::error::RecvError;
 
+use super::queue::MessageReceiver;
 use super::{ClientStatus, Message, MqttClient};
-use crate::{AwsMqttError, Error, Result};
+use crate::{AwsMqttError, Error, Qos, Result};
 
 /// The `CreateMqttFuture` struct represents a future for creating an MQTT
 /// client.
@@ -60,72 +66,194 @@ where
     }
 }
 
-/// The [`SubscribeMessageFuture`] wraps a future that produces a [`Message`].
+/// The [`SubscribeMessageStream`] yields every [`Message`] received on the
+/// subscribed topics until it's dropped.
+///
+/// The messages are handed over from the SDK's event loop which must not be
+/// blocked, so they are buffered. By default the buffer has no limit and a
+/// consumer which stalls grows the memory usage with every received message,
+/// [`ConfigBuilder::with_subscriber_capacity`] bounds it.
+///
+/// [`ConfigBuilder::with_subscriber_capacity`]: super::ConfigBuilder::with_subscriber_capacity
+#[derive(Debug)]
+pub struct SubscribeMessageStream {
+    receiver: MessageReceiver,
+}
+
+impl SubscribeMessageStream {
+    pub(super) fn new(receiver: MessageReceiver) -> Self {
+        Self { receiver }
+    }
+
+    /// Receives the next message, `None` is returned once the client is
+    /// dropped, all the topics of the stream have been unsubscribed or the
+    /// stream has been disconnected by [`LagPolicy::Disconnect`].
+    ///
+    /// [`LagPolicy::Disconnect`]: super::LagPolicy::Disconnect
+    pub async fn recv(&mut self) -> Option<Message> {
+        future::poll_fn(|cx| self.receiver.poll_recv(cx)).await
+    }
+
+    /// Receives the next message without waiting. Like
+    /// [`SubscribeMessageStream::recv`], `None` is returned once the stream has
+    /// ended, and [`Error::NoPendingMessage`] if there isn't any pending
+    /// message yet.
+    pub fn try_recv(&mut self) -> Result<Option<Message>> {
+        match self.receiver.try_recv() {
+            Ok(message) => Ok(Some(message)),
+            Err(TryRecvError::Empty) => Err(Error::NoPendingMessage),
+            Err(TryRecvError::Disconnected) => Ok(None),
+        }
+    }
+
+    /// Receives the next message like [`SubscribeMessageStream::recv`], but
+    /// returns [`Error::Timeout`] if none arrives within the `timeout`.
+    pub async fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<Message>> {
+        Ok(tokio::time::timeout(timeout, self.recv()).await?)
+    }
+}
+
+impl Stream for SubscribeMessageStream {
+    type Item = Message;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+/// The [`TypedSubscriber`] yields the JSON payload of every [`Message`]
+/// received on the subscribed topics deserialized into `T`, until it's dropped.
+#[derive(Debug)]
+pub struct TypedSubscriber<T> {
+    stream: SubscribeMessageStream,
+    _payload: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> TypedSubscriber<T> {
+    pub(super) fn new(stream: SubscribeMessageStream) -> Self {
+        Self {
+            stream,
+            _payload: PhantomData,
+        }
+    }
+
+    /// Receives and deserializes the next message, `None` is returned once the
+    /// client is dropped or all the topics of the subscriber have been
+    /// unsubscribed. A payload which can't be deserialized into `T` yields
+    /// [`Error::Decode`] without ending the subscription.
+    pub async fn recv(&mut self) -> Option<Result<T>> {
+        self.stream.recv().await.map(decode)
+    }
+
+    /// Receives and deserializes the next message without waiting, `None` is
+    /// returned once the subscriber has ended and [`Error::NoPendingMessage`]
+    /// if there isn't any pending message, see
+    /// [`SubscribeMessageStream::try_recv`].
+    pub fn try_recv(&mut self) -> Result<Option<T>> {
+        self.stream.try_recv()?.map(decode).transpose()
+    }
+
+    /// Receives and deserializes the next message, see
+    /// [`SubscribeMessageStream::recv_timeout`].
+    pub async fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<T>> {
+        self.stream
+            .recv_timeout(timeout)
+            .await?
+            .map(decode)
+            .transpose()
+    }
+}
+
+impl<T: DeserializeOwned> Stream for TypedSubscriber<T> {
+    type Item = Result<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.stream)
+            .poll_next(cx)
+            .map(|message| message.map(decode))
+    }
+}
+
+fn decode<T: DeserializeOwned>(message: Message) -> Result<T> {
+    serde_json::from_slice(&message.data).map_err(|source| Error::Decode {
+        topic: message.topic,
+        source,
+    })
+}
+
+/// The `OperationResponseFuture` represents the future that will resolve to the
+/// result of an operation.
 #[pin_project]
-pub struct SubscribeMessageFuture<F>
+pub struct OperationResponseFuture<F>
 where
-    F: Future<Output = StdResult<Message, RecvError>>,
+    F: Future<Output = StdResult<i32, RecvError>>,
 {
     #[pin]
     receiver: F,
 }
 
-impl<F> SubscribeMessageFuture<F>
+impl<F> OperationResponseFuture<F>
 where
-    F: Future<Output = StdResult<Message, RecvError>>,
+    F: Future<Output = StdResult<i32, RecvError>>,
 {
     pub(super) fn new(receiver: F) -> Self {
         Self { receiver }
     }
 }
 
-impl<F> Future for SubscribeMessageFuture<F>
+impl<F> Future for OperationResponseFuture<F>
 where
-    F: Future<Output = StdResult<Message, RecvError>>,
+    F: Future<Output = StdResult<i32, RecvError>>,
 {
-    type Output = Result<Message>;
+    type Output = Result<()>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         match self.project().receiver.poll(cx) {
-            Poll::Ready(Ok(msg)) => Poll::Ready(Ok(msg)),
-            Poll::Ready(Err(_)) => Poll::Ready(Err(Error::AwsReceiveMessage)),
+            Poll::Ready(Ok(0)) => Poll::Ready(Ok(())),
+            Poll::Ready(Ok(error_code)) => Poll::Ready(Err(AwsMqttError::try_from(error_code)
+                .map(Error::from)
+                .unwrap_or(Error::AwsUnknownMqttError(error_code)))),
+            Poll::Ready(Err(_)) => Poll::Ready(Err(Error::AwsReceiveResponse)),
             Poll::Pending => Poll::Pending,
         }
     }
 }
 
-/// The `OperationResponseFuture` represents the future that will resolve to the
-/// result of an operation.
+/// The `SubAckFuture` resolves once the broker has acknowledged a
+/// subscription.
 #[pin_project]
-pub struct OperationResponseFuture<F>
+pub(super) struct SubAckFuture<F>
 where
-    F: Future<Output = StdResult<i32, RecvError>>,
+    F: Future<Output = StdResult<(i32, Qos), RecvError>>,
 {
     #[pin]
     receiver: F,
 }
 
-impl<F> OperationResponseFuture<F>
+impl<F> SubAckFuture<F>
 where
-    F: Future<Output = StdResult<i32, RecvError>>,
+    F: Future<Output = StdResult<(i32, Qos), RecvError>>,
 {
     pub(super) fn new(receiver: F) -> Self {
         Self { receiver }
     }
 }
 
-impl<F> Future for OperationResponseFuture<F>
+impl<F> Future for SubAckFuture<F>
 where
-    F: Future<Output = StdResult<i32, RecvError>>,
+    F: Future<Output = StdResult<(i32, Qos), RecvError>>,
 {
     type Output = Result<()>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         match self.project().receiver.poll(cx) {
-            Poll::Ready(Ok(0)) => Poll::Ready(Ok(())),
-            Poll::Ready(Ok(error_code)) => Poll::Ready(Err(AwsMqttError::try_from(error_code)
-                .map(Error::from)
-                .unwrap_or(Error::AwsUnknownMqttError(error_code)))),
+            Poll::Ready(Ok((0, Qos::QosFailure))) => Poll::Ready(Err(Error::SubscribeRejected)),
+            Poll::Ready(Ok((0, _))) => Poll::Ready(Ok(())),
+            Poll::Ready(Ok((error_code, _))) => {
+                Poll::Ready(Err(AwsMqttError::try_from(error_code)
+                    .map(Error::from)
+                    .unwrap_or(Error::AwsUnknownMqttError(error_code))))
+            }
             Poll::Ready(Err(_)) => Poll::Ready(Err(Error::AwsReceiveResponse)),
             Poll::Pending => Poll::Pending,
         }
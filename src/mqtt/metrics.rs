@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use parking_lot::Mutex;
+
+/// The `MqttMetrics` struct is a snapshot of the counters of a [`MqttClient`]
+/// since its creation.
+///
+/// [`MqttClient`]: super::MqttClient
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MqttMetrics {
+    /// Number of the messages acknowledged by the broker, for QoS 0 it's the
+    /// number of the messages written to the socket.
+    pub messages_published: u64,
+    /// Number of the payload bytes of the published messages, counted like
+    /// `messages_published`.
+    pub bytes_published: u64,
+    /// Number of the publishes which the SDK refused to send or which it
+    /// reported as failed.
+    pub publish_failures: u64,
+    /// Number of the publishes which weren't acknowledged within their
+    /// timeout, they are counted as published if the acknowledgement arrives
+    /// later.
+    pub publish_timeouts: u64,
+    /// Number of the messages received on the subscribed topics.
+    pub messages_received: u64,
+    /// Number of the payload bytes received on the subscribed topics.
+    pub bytes_received: u64,
+    /// Number of the received messages dropped because the buffer of a
+    /// subscriber was full, see [`ConfigBuilder::with_subscriber_capacity`].
+    ///
+    /// [`ConfigBuilder::with_subscriber_capacity`]: super::ConfigBuilder::with_subscriber_capacity
+    pub messages_dropped: u64,
+    /// Number of the times the connection has been interrupted.
+    pub interruptions: u64,
+    /// Number of the times the connection has been resumed after an
+    /// interruption.
+    pub reconnects: u64,
+}
+
+/// Counters updated from the callbacks of the client.
+#[derive(Debug, Default)]
+pub(super) struct Metrics {
+    messages_published: AtomicU64,
+    bytes_published: AtomicU64,
+    // The payload sizes of the publishes waiting for their acknowledgement.
+    pending_publishes: Mutex<HashMap<u16, u64>>,
+    publish_failures: AtomicU64,
+    publish_timeouts: AtomicU64,
+    messages_received: AtomicU64,
+    bytes_received: AtomicU64,
+    messages_dropped: AtomicU64,
+    interruptions: AtomicU64,
+    reconnects: AtomicU64,
+}
+
+impl Metrics {
+    pub(super) fn publish_sent(&self, packet_id: u16, bytes: usize) {
+        self.pending_publishes
+            .lock()
+            .insert(packet_id, bytes as u64);
+    }
+
+    pub(super) fn published(&self, packet_id: u16, error_code: i32) {
+        let bytes = self.pending_publishes.lock().remove(&packet_id);
+
+        if error_code == 0 {
+            self.messages_published.fetch_add(1, Ordering::Relaxed);
+            self.bytes_published
+                .fetch_add(bytes.unwrap_or_default(), Ordering::Relaxed);
+        } else {
+            self.publish_failed();
+        }
+    }
+
+    pub(super) fn publish_failed(&self) {
+        self.publish_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn publish_timed_out(&self) {
+        self.publish_timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn received(&self, bytes: usize) {
+        self.messages_received.fetch_add(1, Ordering::Relaxed);
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(super) fn dropped(&self) {
+        self.messages_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn interrupted(&self) {
+        self.interruptions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn resumed(&self) {
+        self.reconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn snapshot(&self) -> MqttMetrics {
+        MqttMetrics {
+            messages_published: self.messages_published.load(Ordering::Relaxed),
+            bytes_published: self.bytes_published.load(Ordering::Relaxed),
+            publish_failures: self.publish_failures.load(Ordering::Relaxed),
+            publish_timeouts: self.publish_timeouts.load(Ordering::Relaxed),
+            messages_received: self.messages_received.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            messages_dropped: self.messages_dropped.load(Ordering::Relaxed),
+            interruptions: self.interruptions.load(Ordering::Relaxed),
+            reconnects: self.reconnects.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -4,14 +4,44 @@ use std::time::Duration;
 use client::ClientConfig;
 pub(crate) use client::InternalMqttClient;
 pub use client::{ClientStatus, MqttClient};
-pub use futures::{CreateMqttFuture, OperationResponseFuture, SubscribeMessageFuture};
+pub use futures::{
+    CreateMqttFuture, OperationResponseFuture, SubscribeMessageStream, TypedSubscriber,
+};
 use itertools::Itertools;
+pub use metrics::MqttMetrics;
+use topic::validate_topic;
 
 use crate::{Error, Qos, Result};
 
 mod callbacks;
 mod client;
 mod futures;
+mod metrics;
+mod queue;
+mod topic;
+
+/// AWS IoT Core doesn't support the exactly once delivery, it closes the
+/// connection on a QoS 2 publish and never completes the handshake, so only
+/// QoS 0 and 1 are accepted. `QosFailure` is only valid in SUBACK packets.
+fn ensure_supported_qos(qos: Qos) -> Result<()> {
+    match qos {
+        Qos::AtMostOnce | Qos::AtLeastOnce => Ok(()),
+        Qos::ExactlyOnce | Qos::QosFailure => Err(Error::UnsupportedQos(qos)),
+    }
+}
+
+/// Decides what happens to a received message once the buffer of a subscriber
+/// is full, see [`ConfigBuilder::with_subscriber_capacity`]. Waiting for the
+/// subscriber isn't an option, as the messages are handed over from the SDK's
+/// event loop which must not be blocked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LagPolicy {
+    /// Drops the oldest buffered message to make room for the new one.
+    DropOldest,
+    /// Drops the new message and disconnects the subscriber, its stream ends
+    /// once the buffered messages are received. The topics stay subscribed.
+    Disconnect,
+}
 
 /// The struct represents a message in a MQTT broker
 #[derive(Debug, Clone)]
@@ -34,10 +64,56 @@ pub struct Message {
     /// Property indicates whether the message should be retained by the broker.
     /// When a message is published with the `retain` flag set to true, the
     /// broker will store the message and deliver it to any new subscribers
-    /// that join the topic.
+    /// that join the topic. On a received message it's set if the message has
+    /// been delivered from the broker's store, i.e. it's the last known state
+    /// of the topic rather than a new publish.
     pub retain: bool,
 }
 
+/// The last will message published by the broker on behalf of the client once
+/// the client disconnects ungracefully.
+#[derive(Debug)]
+struct Will {
+    topic: CString,
+    payload: Vec<u8>,
+    qos: Qos,
+    retain: bool,
+}
+
+/// The PKCS#11 token which holds the client's private key.
+#[derive(Debug)]
+struct Pkcs11 {
+    lib: CString,
+    user_pin: CString,
+    token_label: Option<CString>,
+    slot_id: Option<u64>,
+    private_key_label: Option<CString>,
+}
+
+/// The HTTP proxy the connection to the broker is tunneled through.
+#[derive(Debug)]
+struct HttpProxy {
+    host: CString,
+    port: u16,
+    auth: Option<(CString, CString)>,
+}
+
+/// The AWS credentials the websocket upgrade request is signed with.
+#[derive(Debug)]
+struct AwsCredentials {
+    access_key_id: CString,
+    secret_access_key: CString,
+    session_token: Option<CString>,
+}
+
+/// The websocket transport, the upgrade request is signed with SigV4 for the
+/// region.
+#[derive(Debug)]
+struct Websocket {
+    region: CString,
+    credentials: Option<AwsCredentials>,
+}
+
 /// The `Config` represents the configuration settings for a MQTT client.
 pub struct Config {
     endpoint: CString,
@@ -50,6 +126,16 @@ pub struct Config {
     password: CString,
     cert: Vec<u8>,
     private_key: Vec<u8>,
+    ca: Vec<u8>,
+    will: Option<Will>,
+    http_proxy: Option<HttpProxy>,
+    pkcs11: Option<Pkcs11>,
+    websocket: Option<Websocket>,
+    reconnect_min_backoff_s: u64,
+    reconnect_max_backoff_s: u64,
+    reconnect_max_attempts: u32,
+    subscriber_capacity: Option<(usize, LagPolicy)>,
+    duplicate_window: Option<usize>,
 }
 
 impl From<&Config> for ClientConfig {
@@ -62,6 +148,91 @@ impl From<&Config> for ClientConfig {
             ping_timeout_ms: config.ping_timeout_ms,
             certificate: config.cert.as_slice().into(),
             private_key: config.private_key.as_slice().into(),
+            ca: config.ca.as_slice().into(),
+            will_topic: config
+                .will
+                .as_ref()
+                .map_or(std::ptr::null(), |will| will.topic.as_ptr()),
+            will_payload: config
+                .will
+                .as_ref()
+                .map(|will| will.payload.as_slice())
+                .unwrap_or_default()
+                .into(),
+            will_qos: config
+                .will
+                .as_ref()
+                .map_or(Qos::AtMostOnce, |will| will.qos),
+            will_retain: config.will.as_ref().is_some_and(|will| will.retain),
+            pkcs11_lib: config
+                .pkcs11
+                .as_ref()
+                .map_or(std::ptr::null(), |pkcs11| pkcs11.lib.as_ptr()),
+            pkcs11_user_pin: config
+                .pkcs11
+                .as_ref()
+                .map_or(std::ptr::null(), |pkcs11| pkcs11.user_pin.as_ptr()),
+            pkcs11_token_label: config
+                .pkcs11
+                .as_ref()
+                .and_then(|pkcs11| pkcs11.token_label.as_ref())
+                .map_or(std::ptr::null(), |label| label.as_ptr()),
+            pkcs11_has_slot_id: config
+                .pkcs11
+                .as_ref()
+                .is_some_and(|pkcs11| pkcs11.slot_id.is_some()),
+            pkcs11_slot_id: config
+                .pkcs11
+                .as_ref()
+                .and_then(|pkcs11| pkcs11.slot_id)
+                .unwrap_or_default(),
+            pkcs11_private_key_label: config
+                .pkcs11
+                .as_ref()
+                .and_then(|pkcs11| pkcs11.private_key_label.as_ref())
+                .map_or(std::ptr::null(), |label| label.as_ptr()),
+            proxy_host: config
+                .http_proxy
+                .as_ref()
+                .map_or(std::ptr::null(), |proxy| proxy.host.as_ptr()),
+            proxy_port: config.http_proxy.as_ref().map_or(0, |proxy| proxy.port),
+            proxy_username: config
+                .http_proxy
+                .as_ref()
+                .and_then(|proxy| proxy.auth.as_ref())
+                .map_or(std::ptr::null(), |(username, _)| username.as_ptr()),
+            proxy_password: config
+                .http_proxy
+                .as_ref()
+                .and_then(|proxy| proxy.auth.as_ref())
+                .map_or(std::ptr::null(), |(_, password)| password.as_ptr()),
+            websocket_region: config
+                .websocket
+                .as_ref()
+                .map_or(std::ptr::null(), |websocket| websocket.region.as_ptr()),
+            websocket_access_key_id: config
+                .websocket
+                .as_ref()
+                .and_then(|websocket| websocket.credentials.as_ref())
+                .map_or(std::ptr::null(), |credentials| {
+                    credentials.access_key_id.as_ptr()
+                }),
+            websocket_secret_access_key: config
+                .websocket
+                .as_ref()
+                .and_then(|websocket| websocket.credentials.as_ref())
+                .map_or(std::ptr::null(), |credentials| {
+                    credentials.secret_access_key.as_ptr()
+                }),
+            websocket_session_token: config
+                .websocket
+                .as_ref()
+                .and_then(|websocket| websocket.credentials.as_ref())
+                .and_then(|credentials| credentials.session_token.as_ref())
+                .map_or(std::ptr::null(), |token| token.as_ptr()),
+            reconnect_min_backoff_s: config.reconnect_min_backoff_s,
+            reconnect_max_backoff_s: config.reconnect_max_backoff_s,
+            reconnect_max_attempts: config.reconnect_max_attempts,
             port: config.port,
             username: config.username.as_ptr(),
             password: config.password.as_ptr(),
@@ -80,9 +251,18 @@ pub struct ConfigBuilder {
     ping_timeout_ms: Option<u32>,
     cert: Option<Vec<u8>>,
     private_key: Option<Vec<u8>>,
+    ca: Option<Vec<u8>>,
     port: Option<u16>,
     username: Option<CString>,
     password: Option<CString>,
+    will: Option<Will>,
+    http_proxy: Option<HttpProxy>,
+    pkcs11: Option<Pkcs11>,
+    websocket: Option<Websocket>,
+    reconnect_backoff: Option<(Duration, Duration)>,
+    reconnect_max_attempts: Option<u32>,
+    subscriber_capacity: Option<(usize, LagPolicy)>,
+    duplicate_window: Option<usize>,
 }
 
 impl ConfigBuilder {
@@ -158,6 +338,17 @@ impl ConfigBuilder {
         self
     }
 
+    /// Sets the certificate authority for a MQTT client. It overrides the
+    /// system trust store, e.g. to trust a private CA or a pinned root.
+    ///
+    /// # Arguments:
+    ///
+    /// * `ca`: PEM encoded certificate authority bundle
+    pub fn with_ca(mut self, ca: impl IntoIterator<Item = u8>) -> Self {
+        self.ca = Some(ca.into_iter().collect_vec());
+        self
+    }
+
     /// The `with_port` function sets the port value for MQTT broker
     pub fn with_port(mut self, port: u16) -> Self {
         self.port = Some(port);
@@ -176,7 +367,205 @@ impl ConfigBuilder {
         Ok(self)
     }
 
+    /// Sets the last will message for a MQTT client. The broker publishes it
+    /// once the client disconnects ungracefully.
+    ///
+    /// # Arguments:
+    ///
+    /// - `topic`: The topic the will message is published to.
+    /// - `payload`: Payload of the will message.
+    /// - `qos`: Quality of Service the will message is published with, see
+    ///   [`MqttClient::publish`] for the supported ones.
+    /// - `retain`: Whether the broker should retain the will message.
+    pub fn with_will(
+        mut self,
+        topic: &str,
+        payload: impl IntoIterator<Item = u8>,
+        qos: Qos,
+        retain: bool,
+    ) -> Result<Self> {
+        ensure_supported_qos(qos)?;
+        validate_topic(topic)?;
+
+        self.will = Some(Will {
+            topic: CString::new(topic).map_err(|_| Error::InvalidTopic(topic.to_owned()))?,
+            payload: payload.into_iter().collect_vec(),
+            qos,
+            retain,
+        });
+        Ok(self)
+    }
+
+    /// Sets the PKCS#11 token, e.g. a TPM or a secure element, which holds the
+    /// private key of a MQTT client, so the key never leaves the token. The
+    /// certificate is still set by [`ConfigBuilder::with_cert`] and
+    /// [`ConfigBuilder::with_private_key`] isn't needed.
+    ///
+    /// # Arguments:
+    ///
+    /// - `lib`: Path to the PKCS#11 library of the token.
+    /// - `user_pin`: The user PIN to log into the token.
+    /// - `token_label`: The label of the token, if it should be looked up by
+    ///   the label.
+    /// - `slot_id`: The id of the slot the token is in, if it should be looked
+    ///   up by the slot.
+    /// - `private_key_label`: The label of the private key object, if the token
+    ///   holds more than one private key.
+    pub fn with_pkcs11(
+        mut self,
+        lib: &str,
+        user_pin: &str,
+        token_label: Option<&str>,
+        slot_id: Option<u64>,
+        private_key_label: Option<&str>,
+    ) -> Result<Self> {
+        self.pkcs11 = Some(Pkcs11 {
+            lib: CString::new(lib)?,
+            user_pin: CString::new(user_pin)?,
+            token_label: token_label.map(CString::new).transpose()?,
+            slot_id,
+            private_key_label: private_key_label.map(CString::new).transpose()?,
+        });
+        Ok(self)
+    }
+
+    /// Sets the HTTP proxy a MQTT client connects to the broker through, the
+    /// connection is tunneled with the `CONNECT` method.
+    ///
+    /// # Arguments:
+    ///
+    /// - `host`: The host name of the proxy.
+    /// - `port`: The port of the proxy.
+    /// - `auth`: The username and password for the basic authentication, if the
+    ///   proxy requires it.
+    pub fn with_http_proxy(
+        mut self,
+        host: &str,
+        port: u16,
+        auth: Option<(&str, &str)>,
+    ) -> Result<Self> {
+        self.http_proxy = Some(HttpProxy {
+            host: CString::new(host).map_err(Error::StringConversion)?,
+            port,
+            auth: auth
+                .map(|(username, password)| {
+                    Ok::<_, Error>((CString::new(username)?, CString::new(password)?))
+                })
+                .transpose()?,
+        });
+        Ok(self)
+    }
+
+    /// Connects a MQTT client to the broker over a websocket (`wss://`, port
+    /// 443 unless [`ConfigBuilder::with_port`] overrides it), e.g. behind a
+    /// firewall which only lets HTTPS through. The upgrade request is signed
+    /// with SigV4, the SDK doesn't offer an unsigned websocket connection to
+    /// AWS IoT Core. The certificate, private key and PKCS#11 token aren't used
+    /// then.
+    ///
+    /// # Arguments:
+    ///
+    /// - `region`: The AWS region the request is signed for.
+    /// - `credentials`: The access key id, secret access key and optional
+    ///   session token to sign with. If they aren't set, the default chain
+    ///   looks them up in the environment variables, the profile files and the
+    ///   ECS or EC2 instance metadata.
+    pub fn with_websocket(
+        mut self,
+        region: &str,
+        credentials: Option<(&str, &str, Option<&str>)>,
+    ) -> Result<Self> {
+        self.websocket = Some(Websocket {
+            region: CString::new(region)?,
+            credentials: credentials
+                .map(|(access_key_id, secret_access_key, session_token)| {
+                    Ok::<_, Error>(AwsCredentials {
+                        access_key_id: CString::new(access_key_id)?,
+                        secret_access_key: CString::new(secret_access_key)?,
+                        session_token: session_token.map(CString::new).transpose()?,
+                    })
+                })
+                .transpose()?,
+        });
+        Ok(self)
+    }
+
+    /// Sets the reconnect backoff for a MQTT client. After the connection is
+    /// interrupted, the delay between reconnect attempts starts at `min` and
+    /// doubles after every failed attempt up to `max`.
+    ///
+    /// # Arguments:
+    ///
+    /// - `min`: The delay before the first reconnect attempt, it's rounded down
+    ///   to whole seconds.
+    /// - `max`: The upper bound of the delay, it's rounded down to whole
+    ///   seconds.
+    ///
+    /// [`ConfigBuilder::build`] returns [`Error::InvalidReconnectBackoff`] if
+    /// any of the delays is shorter than a second or `min` is greater than
+    /// `max`.
+    pub fn with_reconnect_backoff(mut self, min: Duration, max: Duration) -> Self {
+        self.reconnect_backoff = Some((min, max));
+        self
+    }
+
+    /// Sets the maximum number of consecutive failed connection attempts,
+    /// after which the client stops reconnecting and the connection is
+    /// closed. By default, or if `attempts` is 0, the client reconnects
+    /// indefinitely.
+    pub fn with_reconnect_max_attempts(mut self, attempts: u32) -> Self {
+        self.reconnect_max_attempts = Some(attempts);
+        self
+    }
+
+    /// Bounds the number of the received messages buffered for every
+    /// subscription stream of a MQTT client. By default the buffer has no
+    /// limit. The dropped messages are counted in
+    /// [`MqttMetrics::messages_dropped`].
+    ///
+    /// # Arguments:
+    ///
+    /// - `capacity`: The maximum number of the buffered messages, a capacity of
+    ///   0 is treated as 1.
+    /// - `policy`: What happens to a message received once the buffer is full.
+    pub fn with_subscriber_capacity(mut self, capacity: usize, policy: LagPolicy) -> Self {
+        self.subscriber_capacity = Some((capacity.max(1), policy));
+        self
+    }
+
+    /// Suppresses the redeliveries of QoS 1 messages, e.g. after the
+    /// connection is resumed, so a message isn't handed to the subscribers
+    /// twice. By default every redelivery is handed over.
+    ///
+    /// A message with the dup flag set is dropped if a message with the same
+    /// topic and payload is among the last `window` QoS 1 messages received on
+    /// the same filter. The SDK doesn't expose the packet id of a PUBLISH, so
+    /// the messages are told apart only by their topic and payload.
+    ///
+    /// # Arguments:
+    ///
+    /// - `window`: The number of the recent messages remembered, a window of 0
+    ///   is treated as 1.
+    pub fn with_duplicate_suppression(mut self, window: usize) -> Self {
+        self.duplicate_window = Some(window.max(1));
+        self
+    }
+
     pub fn build(self) -> Result<Config> {
+        if let Some((min, max)) = self.reconnect_backoff {
+            if min.as_secs() == 0 || max.as_secs() == 0 || min > max {
+                return Err(Error::InvalidReconnectBackoff(min, max));
+            }
+        }
+
+        // the private key stays in the token, but the certificate is still needed
+        if self.websocket.is_none()
+            && self.pkcs11.is_some()
+            && self.cert.as_ref().map_or(true, Vec::is_empty)
+        {
+            return Err(Error::MissCertificate);
+        }
+
         Ok(Config {
             endpoint: self.endpoint.ok_or(Error::MissEndpoint)?,
             client_id: self.client_id.ok_or(Error::MissClientId)?,
@@ -185,9 +574,19 @@ impl ConfigBuilder {
             ping_timeout_ms: self.ping_timeout_ms.unwrap_or(500),
             cert: self.cert.unwrap_or_default(),
             private_key: self.private_key.unwrap_or_default(),
+            ca: self.ca.unwrap_or_default(),
             port: self.port.unwrap_or_default(),
             username: self.username.unwrap_or_default(),
             password: self.password.unwrap_or_default(),
+            will: self.will,
+            http_proxy: self.http_proxy,
+            pkcs11: self.pkcs11,
+            websocket: self.websocket,
+            reconnect_min_backoff_s: self.reconnect_backoff.map_or(0, |(min, _)| min.as_secs()),
+            reconnect_max_backoff_s: self.reconnect_backoff.map_or(0, |(_, max)| max.as_secs()),
+            reconnect_max_attempts: self.reconnect_max_attempts.unwrap_or_default(),
+            subscriber_capacity: self.subscriber_capacity,
+            duplicate_window: self.duplicate_window,
         })
     }
 }
@@ -0,0 +1,127 @@
+//! The buffer the received messages are handed over through from the SDK's
+//! event loop to a [`SubscribeMessageStream`]. The event loop must never be
+//! blocked, so a full buffer is handled by the [`LagPolicy`] of the client
+//! instead of waiting for the consumer.
+//!
+//! [`SubscribeMessageStream`]: super::SubscribeMessageStream
+
+use std::collections::VecDeque;
+use std::result::Result as StdResult;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::task::AtomicWaker;
+use parking_lot::Mutex;
+use tokio::sync::mpsc::error::TryRecvError;
+
+use super::metrics::Metrics;
+use super::{LagPolicy, Message};
+
+#[derive(Debug, Default)]
+struct Shared {
+    messages: Mutex<VecDeque<Message>>,
+    waker: AtomicWaker,
+    sender_dropped: AtomicBool,
+    receiver_dropped: AtomicBool,
+}
+
+/// Creates a queue of the messages of a subscriber, it's unbounded if there
+/// isn't any `capacity`.
+pub(super) fn message_queue(
+    capacity: Option<(usize, LagPolicy)>,
+    metrics: Arc<Metrics>,
+) -> (MessageSender, MessageReceiver) {
+    let shared = Arc::new(Shared::default());
+
+    (
+        MessageSender {
+            shared: shared.clone(),
+            capacity,
+            metrics,
+        },
+        MessageReceiver { shared },
+    )
+}
+
+#[derive(Debug)]
+pub(super) struct MessageSender {
+    shared: Arc<Shared>,
+    capacity: Option<(usize, LagPolicy)>,
+    metrics: Arc<Metrics>,
+}
+
+impl MessageSender {
+    /// Queues the message, `false` is returned if the buffer is full and the
+    /// subscriber has to be disconnected.
+    pub(super) fn send(&self, message: Message) -> bool {
+        let mut messages = self.shared.messages.lock();
+
+        if let Some((capacity, policy)) = self.capacity {
+            if messages.len() >= capacity {
+                self.metrics.dropped();
+
+                match policy {
+                    LagPolicy::DropOldest => {
+                        messages.pop_front();
+                    }
+                    LagPolicy::Disconnect => return false,
+                }
+            }
+        }
+
+        messages.push_back(message);
+        drop(messages);
+
+        self.shared.waker.wake();
+        true
+    }
+
+    /// Checks whether the receiving stream has been dropped.
+    pub(super) fn is_closed(&self) -> bool {
+        self.shared.receiver_dropped.load(Ordering::Acquire)
+    }
+}
+
+impl Drop for MessageSender {
+    fn drop(&mut self) {
+        self.shared.sender_dropped.store(true, Ordering::Release);
+        self.shared.waker.wake();
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct MessageReceiver {
+    shared: Arc<Shared>,
+}
+
+impl MessageReceiver {
+    pub(super) fn try_recv(&mut self) -> StdResult<Message, TryRecvError> {
+        // The flag is read before the buffer, so a message queued right before the
+        // sender is dropped is still received.
+        let sender_dropped = self.shared.sender_dropped.load(Ordering::Acquire);
+
+        match self.shared.messages.lock().pop_front() {
+            Some(message) => Ok(message),
+            None if sender_dropped => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    pub(super) fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<Message>> {
+        self.shared.waker.register(cx.waker());
+
+        match self.try_recv() {
+            Ok(message) => Poll::Ready(Some(message)),
+            Err(TryRecvError::Empty) => Poll::Pending,
+            Err(TryRecvError::Disconnected) => Poll::Ready(None),
+        }
+    }
+}
+
+impl Drop for MessageReceiver {
+    fn drop(&mut self) {
+        self.shared.receiver_dropped.store(true, Ordering::Release);
+        self.shared.messages.lock().clear();
+    }
+}
@@ -0,0 +1,124 @@
+//! Validation of the topic names and filters before they are handed to the
+//! broker, AWS IoT Core closes the connection on a malformed topic.
+
+use crate::{Error, Result};
+
+/// AWS IoT Core limits a topic to 256 bytes.
+const MAX_TOPIC_LEN: usize = 256;
+/// AWS IoT Core limits a topic to 7 forward slashes, the 3 slashes of the Basic
+/// Ingest prefix (`$aws/rules/<rule-name>/`) aren't counted.
+const MAX_TOPIC_SLASHES: usize = 7;
+const BASIC_INGEST_PREFIX: &str = "$aws/rules/";
+const BASIC_INGEST_SLASHES: usize = 3;
+
+/// Validates a topic name a message is published to, the wildcards aren't
+/// allowed in it.
+pub(super) fn validate_topic(topic: &str) -> Result<()> {
+    validate(topic)?;
+
+    if topic.contains(['+', '#']) {
+        return Err(Error::InvalidTopic(topic.to_owned()));
+    }
+
+    Ok(())
+}
+
+/// Validates a topic filter a client subscribes to. `+` has to occupy a whole
+/// level and `#` has to be the last level.
+pub(super) fn validate_topic_filter(filter: &str) -> Result<()> {
+    validate(filter)?;
+
+    let mut levels = filter.split('/').peekable();
+    while let Some(level) = levels.next() {
+        let valid = match level {
+            "+" => true,
+            "#" => levels.peek().is_none(),
+            level => !level.contains(['+', '#']),
+        };
+
+        if !valid {
+            return Err(Error::InvalidTopic(filter.to_owned()));
+        }
+    }
+
+    Ok(())
+}
+
+fn validate(topic: &str) -> Result<()> {
+    let mut slashes = topic.matches('/').count();
+    if topic.starts_with(BASIC_INGEST_PREFIX) {
+        slashes = slashes.saturating_sub(BASIC_INGEST_SLASHES);
+    }
+
+    if topic.is_empty()
+        || topic.len() > MAX_TOPIC_LEN
+        || slashes > MAX_TOPIC_SLASHES
+        || topic.contains('\0')
+    {
+        return Err(Error::InvalidTopic(topic.to_owned()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_valid_topics() {
+        assert!(validate_topic("devices/thing/telemetry").is_ok());
+        assert!(validate_topic_filter("devices/thing/telemetry").is_ok());
+    }
+
+    #[test]
+    fn rejects_empty_topic() {
+        assert!(validate_topic("").is_err());
+        assert!(validate_topic_filter("").is_err());
+    }
+
+    #[test]
+    fn limits_topic_length() {
+        assert!(validate_topic(&"a".repeat(MAX_TOPIC_LEN)).is_ok());
+        assert!(validate_topic(&"a".repeat(MAX_TOPIC_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn limits_topic_slashes() {
+        assert!(validate_topic("a/b/c/d/e/f/g/h").is_ok());
+        assert!(validate_topic("a/b/c/d/e/f/g/h/i").is_err());
+    }
+
+    #[test]
+    fn ignores_basic_ingest_prefix_slashes() {
+        assert!(validate_topic("$aws/rules/rule/a/b/c/d/e/f/g/h").is_ok());
+        assert!(validate_topic("$aws/rules/rule/a/b/c/d/e/f/g/h/i").is_err());
+    }
+
+    #[test]
+    fn counts_reserved_topic_slashes() {
+        assert!(validate_topic("$aws/things/thing/shadow/a/b/c/d").is_ok());
+        assert!(validate_topic("$aws/things/thing/shadow/a/b/c/d/e/f/g").is_err());
+    }
+
+    #[test]
+    fn rejects_wildcards_in_topic() {
+        assert!(validate_topic("devices/+/telemetry").is_err());
+        assert!(validate_topic("devices/#").is_err());
+    }
+
+    #[test]
+    fn validates_single_level_wildcard() {
+        assert!(validate_topic_filter("devices/+/telemetry").is_ok());
+        assert!(validate_topic_filter("+").is_ok());
+        assert!(validate_topic_filter("a/b+").is_err());
+    }
+
+    #[test]
+    fn validates_multi_level_wildcard() {
+        assert!(validate_topic_filter("devices/#").is_ok());
+        assert!(validate_topic_filter("#").is_ok());
+        assert!(validate_topic_filter("a/#/c").is_err());
+        assert!(validate_topic_filter("a#").is_err());
+    }
+}
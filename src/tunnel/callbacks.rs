@@ -200,14 +200,15 @@ pub(super) fn create_message_received_callback(
 }
 
 pub(super) fn create_stream_started_callback(
-    stream_start_tx: mpsc::Sender<u32>,
+    stream_start_tx: mpsc::Sender<(u32, Option<String>)>,
 ) -> impl Fn(i32, u32, Buffer) {
     move |error_code, connection_id, service_id| {
+        let service_id = String::try_from(service_id).ok();
+        debug!(%error_code, %connection_id, ?service_id, "stream started");
+
         if error_code == 0 {
-            let _ = stream_start_tx.try_send(connection_id);
+            let _ = stream_start_tx.try_send((connection_id, service_id));
         }
-
-        debug!(%error_code, %connection_id, service_id = ?String::try_from(service_id), "stream started");
     }
 }
 
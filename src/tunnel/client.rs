@@ -1,17 +1,15 @@
+use std::collections::HashMap;
 use std::ffi::{c_char, c_void, CString};
-use std::io::{self, ErrorKind};
-use std::net::SocketAddrV4;
 use std::ops::Deref;
 use std::sync::Arc;
 
-use futures::future::BoxFuture;
+use futures::future::{BoxFuture, Either};
 use futures::{future, Future, FutureExt};
 use parking_lot::{const_fair_mutex, const_mutex, FairMutex, Mutex};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
 use tokio::sync::{broadcast, mpsc, Mutex as TokioMutex, Notify};
 use tokio::task::JoinHandle;
-use tracing::debug;
+use tracing::{debug, warn};
 
 use super::callbacks::{
     create_connection_failure_callback, create_connection_reset_callback,
@@ -21,6 +19,7 @@ use super::callbacks::{
     create_subscribe_complete_callback, create_subscribe_tunnel_callback, ClientInterface,
     Credentials, TunnelInterface,
 };
+use super::LocalConnector;
 use crate::common::{Buffer, SharedPtr, UniquePtr};
 use crate::mqtt::{InternalMqttClient, MqttClient};
 use crate::{ApiHandle, Error, Qos, Result};
@@ -98,6 +97,7 @@ pub struct TunnelClient {
     _mqtt_client: Arc<MqttClient>,
     _interface: Arc<ClientInterface>,
     sessions: Arc<FairMutex<Vec<JoinHandle<Result<()>>>>>,
+    services: Arc<FairMutex<HashMap<String, Arc<dyn LocalConnector>>>>,
     drop_client: Arc<Notify>,
     task: JoinHandle<()>,
 }
@@ -123,7 +123,24 @@ impl Drop for TunnelClient {
 }
 
 impl TunnelClient {
-    pub async fn create(mqtt_client: Arc<MqttClient>, qos: Qos, thing_name: &str) -> Result<Self> {
+    /// Creates a tunnel client which listens for the tunnel notifications of
+    /// the thing and forwards every opened tunnel to a local service.
+    ///
+    /// # Arguments:
+    ///
+    /// - `mqtt_client`: The client the tunnel notifications are received with.
+    /// - `qos`: Quality of Service of the notification subscription.
+    /// - `thing_name`: The name of the thing the tunnels are opened for.
+    /// - `destination`: The local service the tunnels are forwarded to, unless
+    ///   the service of the tunnel has its own destination set through
+    ///   [`TunnelClient::set_service_destination`]. A
+    ///   [`SocketAddr`](std::net::SocketAddr) forwards to a local TCP service.
+    pub async fn create(
+        mqtt_client: Arc<MqttClient>,
+        qos: Qos,
+        thing_name: &str,
+        destination: impl LocalConnector + 'static,
+    ) -> Result<Self> {
         ApiHandle::handle();
 
         let (create_client_tx, mut create_client_rx) = mpsc::channel::<i32>(1);
@@ -156,6 +173,7 @@ impl TunnelClient {
         }
 
         let sessions = Arc::new(const_fair_mutex(Vec::new()));
+        let services = Arc::new(const_fair_mutex(HashMap::new()));
         let drop_client = Arc::new(Notify::new());
 
         let tunnel_client = TunnelClient {
@@ -165,9 +183,12 @@ impl TunnelClient {
             task: tokio::spawn(subscribe(
                 tunnel_created_rx,
                 sessions.clone(),
+                Arc::new(destination),
+                services.clone(),
                 drop_client.clone(),
             )),
             sessions,
+            services,
             drop_client,
         };
 
@@ -182,11 +203,31 @@ impl TunnelClient {
             None => Err(Error::TunnelClientCreate),
         }
     }
+
+    /// Sets the local service the tunnels of the service id are forwarded to,
+    /// e.g. `"HTTP"` to a local web server while `"SSH"` uses the default
+    /// destination.
+    ///
+    /// # Arguments:
+    ///
+    /// - `service_id`: The service id as configured for the tunnel.
+    /// - `destination`: The local service the service id is forwarded to.
+    pub fn set_service_destination(
+        &self,
+        service_id: &str,
+        destination: impl LocalConnector + 'static,
+    ) {
+        self.services
+            .lock()
+            .insert(service_id.to_owned(), Arc::new(destination));
+    }
 }
 
 async fn subscribe(
     mut tunnel_created_rx: mpsc::Receiver<Credentials>,
     sessions: Arc<FairMutex<Vec<JoinHandle<Result<()>>>>>,
+    destination: Arc<dyn LocalConnector>,
+    services: Arc<FairMutex<HashMap<String, Arc<dyn LocalConnector>>>>,
     drop_client_notify: Arc<Notify>,
 ) {
     loop {
@@ -205,16 +246,24 @@ async fn subscribe(
             sessions.push(tokio::spawn(tunnel(
                 format!("data.tunneling.iot.{region}.amazonaws.com"),
                 access_token,
+                destination.clone(),
+                services.clone(),
                 drop_client_notify.clone(),
             )));
         }
     }
 }
 
-async fn tunnel(endpoint: String, access_token: String, drop_client: Arc<Notify>) -> Result<()> {
+async fn tunnel(
+    endpoint: String,
+    access_token: String,
+    destination: Arc<dyn LocalConnector>,
+    services: Arc<FairMutex<HashMap<String, Arc<dyn LocalConnector>>>>,
+    drop_client: Arc<Notify>,
+) -> Result<()> {
     let (shutdown_tx, shutdown_rx) = shutdown_channels();
     let (message_receive_tx, message_receive_rx) = mpsc::channel::<Message>(20);
-    let (stream_start_tx, mut stream_start_rx) = mpsc::channel::<u32>(1);
+    let (stream_start_tx, mut stream_start_rx) = mpsc::channel::<(u32, Option<String>)>(1);
 
     let tunnel_interface = TunnelInterface {
         connection_success: Box::new(create_connection_success_callback()),
@@ -252,46 +301,61 @@ async fn tunnel(endpoint: String, access_token: String, drop_client: Arc<Notify>
         return Err(Error::TunnelCreate);
     }
 
-    let Some(connection_id) = stream_start_rx.recv().await else {
+    let Some((connection_id, service_id)) = stream_start_rx.recv().await else {
         drop_tunnel(internal_tunnel.clone(), shutdown_rx.cancel()).await;
         return Err(Error::TunnelCreate);
     };
 
-    debug!(%connection_id, "stream started");
+    // V1 tunnels have no service id, they always go to the default destination
+    let destination = service_id
+        .as_ref()
+        .and_then(|service_id| services.lock().get(service_id).cloned())
+        .unwrap_or(destination);
+
+    debug!(%connection_id, ?service_id, "stream started");
 
     let cancel = future::select_all([
         erase_return(shutdown_rx.cancel()),
         erase_return(drop_client.notified()),
     ]);
 
-    future::select_all([
-        erase_return(ssh(
+    let forwarded = future::select(
+        forward(
             internal_tunnel.clone(),
-            22,
+            destination,
             connection_id,
             message_receive_rx,
-        )),
-        erase_return(cancel),
-    ])
+        )
+        .boxed(),
+        cancel,
+    )
     .await;
 
+    // a cancelled tunnel isn't an error, only a failed forwarding is
+    let res = match forwarded {
+        Either::Left((res, _)) => res,
+        Either::Right(_) => Ok(()),
+    };
+
     drop_tunnel(internal_tunnel.clone(), shutdown_rx.cancel()).await;
+
+    if let Err(err) = &res {
+        warn!(%err, %connection_id, "forwarding of the tunnel failed");
+    }
     debug!("exited from tunnel");
 
-    Ok(())
+    res
 }
 
-async fn ssh(
+async fn forward(
     internal_tunnel: Arc<TokioMutex<InternalTunnelPointer>>,
-    port: u16,
+    destination: Arc<dyn LocalConnector>,
     connection_id: u32,
     mut packets: mpsc::Receiver<Message>,
 ) -> Result<()> {
-    let stream = TcpStream::connect::<SocketAddrV4>(SocketAddrV4::new([127, 0, 0, 1].into(), port))
-        .await
-        .map_err(Error::IoError)?;
+    let stream = destination.connect().await.map_err(Error::IoError)?;
 
-    let (mut reader, mut writer) = stream.into_split();
+    let (mut reader, mut writer) = tokio::io::split(stream);
 
     let tunnel = internal_tunnel.clone();
     let reader_task: JoinHandle<Result<()>> = tokio::spawn(async move {
@@ -317,10 +381,7 @@ async fn ssh(
             };
 
             if send_res != 0 {
-                return Err(Error::IoError(io::Error::new(
-                    ErrorKind::Other,
-                    "couldn't send a message",
-                )));
+                return Err(Error::TunnelSendMessage(send_res));
             }
         }
     });
@@ -332,13 +393,9 @@ async fn ssh(
         Ok(())
     });
 
-    let _ = future::try_join(reader_task, writer_task)
-        .await
-        .map_err(|_err| {
-            Error::IoError(io::Error::new(ErrorKind::Other, "ssh connection failed"))
-        })?;
+    let (reader_res, writer_res) = future::try_join(reader_task, writer_task).await?;
 
-    Ok(())
+    reader_res.and(writer_res)
 }
 
 async fn drop_tunnel(
@@ -0,0 +1,31 @@
+use std::io;
+use std::net::SocketAddr;
+
+use futures::future::BoxFuture;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+
+/// A bidirectional byte stream to a local service, which a tunnel stream is
+/// forwarded to.
+pub trait LocalStream: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T> LocalStream for T where T: AsyncRead + AsyncWrite + Send + Unpin {}
+
+/// The `LocalConnector` opens the connection to the local service a tunnel is
+/// forwarded to, e.g. a TCP connection to a SSH server or a unix socket.
+pub trait LocalConnector: Send + Sync {
+    /// Connects to the local service, it's called once for every started
+    /// stream of the tunnel.
+    fn connect(&self) -> BoxFuture<'static, io::Result<Box<dyn LocalStream>>>;
+}
+
+/// Forwards the tunnel to the TCP service listening on the address.
+impl LocalConnector for SocketAddr {
+    fn connect(&self) -> BoxFuture<'static, io::Result<Box<dyn LocalStream>>> {
+        let addr = *self;
+        Box::pin(async move {
+            let stream = TcpStream::connect(addr).await?;
+            Ok(Box::new(stream) as Box<dyn LocalStream>)
+        })
+    }
+}
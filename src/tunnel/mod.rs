@@ -1,4 +1,6 @@
 pub use client::TunnelClient;
+pub use connector::{LocalConnector, LocalStream};
 
 mod callbacks;
 mod client;
+mod connector;